@@ -1,25 +1,24 @@
-use std::detect::__is_feature_detected::xsave;
-use std::error;
-use std::error::Error;
-use std::ops::ControlFlow::Continue;
-use std::ops::Deref;
-
-use crossterm::cursor::position;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ropey::Rope;
 
 use crate::args::Args;
-use crate::event::{flush_resize_events, Event, EventHandler};
-use crate::extension::graphemes_ex::{
-    next_grapheme_boundary, nth_prev_grapheme_boundary, prev_grapheme_boundary,
-};
-use crate::extension::rope::{Line, RopeSliceEx};
+use crate::clipboard;
+use crate::command::Command;
+use crate::diff;
+use crate::disk::DiskChange;
+use crate::event::{Event, EventHandler};
+use crate::extension::rope::Line;
+use crate::extension::word::WordKind;
+use crate::history::ViewState;
+use crate::register::{Registers, CLIPBOARD_REGISTER, DEFAULT_REGISTER};
 use crate::render::banner::Banner;
 use crate::render::document::Document;
-use crate::render::message::MessageBar;
+use crate::render::hex;
 use crate::render::switcher::DocumentSwitcher;
 use crate::render::Render;
+use crate::screen;
 use crate::screen::{Position, Screen};
-use crate::{screen, DEFAULT_FILENAME, DEFAULT_FILETYPE};
+use crate::selection::{Block, Range, Selection};
 
 /// global result.
 pub type AppResult<T> = Result<T, anyhow::Error>;
@@ -38,8 +37,39 @@ pub struct App {
     doc_switcher: DocumentSwitcher,
     /// cursor 的偏移量,即超出屏幕的部分
     offset: Position,
-    ///   cursor position
-    cursor: Position,
+    /// the cursors, the screen follows the primary one
+    selection: Selection,
+    /// the block being selected, the selection holds its ranges
+    block: Option<Block>,
+    /// cut / copied text, shared by all documents
+    registers: Registers,
+    /// the register used by the next cut / copy / paste
+    register: char,
+    /// the next key names the register, see [`App::register`]
+    awaiting_register: bool,
+    /// the next hex digit typed into a binary document goes into the low nibble of the byte
+    low_nibble: bool,
+    /// the display column of every cursor when it started moving up / down, see [`App::move_vertically`]
+    columns: Vec<usize>,
+    /// the command being typed into the command line
+    command: Option<String>,
+    /// waiting for the terminal to send the system clipboard
+    clipboard: Option<clipboard::Read>,
+    /// the action waiting for an answer of the save / discard / cancel prompt, or the keep / take / diff one
+    pending: Option<Pending>,
+}
+
+/// an action that would lose unsaved changes, it only runs after the user answers the prompt.
+#[derive(Copy, Clone, Debug)]
+enum Pending {
+    /// quit editor
+    Quit,
+    /// close current document
+    Close,
+    /// the document at the index changed on disk while it has unsaved changes
+    Changed(usize),
+    /// the document at the index has the swap file of a crashed session
+    Recover(usize),
 }
 
 #[derive(Clone)]
@@ -49,6 +79,8 @@ pub struct AppCtx {
     pub offset: Position,
     //   cursor position
     pub cursor: Position,
+    // every cursor with its selected text
+    pub selection: Selection,
     // screen size
     pub screen_size: (u16, u16),
     pub doc_size: (usize, usize),
@@ -61,8 +93,8 @@ impl AppCtx {
         Position {
             x: self
                 .current_line
-                .get_offset(self.cursor.x.saturating_sub(self.offset.x)),
-            // x: self.cursor.x.saturating_sub(self.offset.x),
+                .get_offset(self.cursor.x)
+                .saturating_sub(self.current_line.origin(self.offset.x)),
             y: self.cursor.y.saturating_sub(self.offset.y),
         }
     }
@@ -89,7 +121,7 @@ impl App {
     ///
     /// # Examples
     ///
-    /// ```
+    /// ```no_run
     ///use raw::app::{App, AppResult};
     ///
     ///fn main() -> AppResult<()> {
@@ -101,17 +133,31 @@ impl App {
         let mut doc_switcher = DocumentSwitcher::default();
 
         let args = Args::load();
+        if let Some(tab_width) = args.tab_width {
+            doc_switcher.set_tab_width(tab_width);
+        }
         doc_switcher.load(args.filenames);
 
-        Self {
+        let mut app = Self {
             running: true,
             events: EventHandler::new(tick_rate),
             screen: Default::default(),
             banner: Default::default(),
             doc_switcher,
             offset: Default::default(),
-            cursor: Default::default(),
-        }
+            selection: Selection::default(),
+            block: None,
+            registers: Registers::default(),
+            register: DEFAULT_REGISTER,
+            awaiting_register: false,
+            low_nibble: false,
+            columns: Vec::new(),
+            command: None,
+            clipboard: None,
+            pending: None,
+        };
+        app.ask_recovery(0);
+        app
     }
 
     /// run app
@@ -126,6 +172,7 @@ impl App {
             }
         }
 
+        self.doc_switcher.remove_swaps();
         self.exit()
     }
 
@@ -136,6 +183,11 @@ impl App {
             Event::Key(event) => {
                 self.on_keypress(event);
             }
+            Event::Paste(text) => self.on_paste(text),
+            Event::FocusGained => {
+                self.check_disk();
+                self.refresh_screen()?;
+            }
             // resize mouse discard
             _ => {}
         }
@@ -148,13 +200,17 @@ impl App {
         let ctx = self.new_ctx();
         let buf = self.screen.get_buf();
         if self.doc_switcher.is_empty() {
-            self.banner.render(ctx, buf, buf.area);
+            self.banner.render(ctx.clone(), buf, buf.area);
+            self.doc_switcher.render_message(ctx, buf, buf.area);
             self.screen.refresh()?;
 
             return Ok(());
         }
 
-        let position = ctx.get_cursor();
+        let mut position = ctx.get_cursor();
+        if self.low_nibble {
+            position.x += 1;
+        }
         screen::move_to(position)?;
 
         self.doc_switcher.render(ctx, buf, buf.area);
@@ -164,117 +220,988 @@ impl App {
 
     /// on key press
     fn on_keypress(&mut self, key: KeyEvent) {
+        if let Some(mut read) = self.clipboard.take() {
+            match read.feed(key) {
+                clipboard::Feed::Pending => {
+                    self.clipboard = Some(read);
+                    return;
+                }
+                clipboard::Feed::Done(text) => {
+                    self.paste_clipboard(text);
+                    self.scroll();
+                    return;
+                }
+                // the terminal can't read the clipboard, handle the key as usual.
+                clipboard::Feed::Unexpected => self.paste_clipboard(None),
+            }
+        }
+
+        if let Some(pending) = self.pending {
+            self.on_prompt(pending, key);
+            return;
+        }
+
+        if self.command.is_some() {
+            self.on_command_key(key);
+            return;
+        }
+
+        if self.awaiting_register {
+            self.awaiting_register = false;
+            self.doc_switcher.clear_message();
+            if let KeyCode::Char(name) = key.code {
+                self.register = name;
+            }
+            return;
+        }
+
+        // any other key ends the block, its ranges stay selected.
+        let block = self.block.take();
+        // and moves on from the nibble typed last.
+        let low_nibble = std::mem::take(&mut self.low_nibble);
+        // only moving up / down again keeps the columns the cursors started in.
+        let columns = std::mem::take(&mut self.columns);
         match (key.code, key.modifiers) {
             // handler quit editor
-            (KeyCode::Char('q'), KeyModifiers::CONTROL) => {
-                self.running = false;
+            (KeyCode::Char('q'), KeyModifiers::CONTROL) => self.quit(),
+
+            // close current document
+            (KeyCode::Char('w'), KeyModifiers::CONTROL) => self.close(),
+
+            // save
+            (KeyCode::Char('s'), KeyModifiers::CONTROL) => {
+                self.doc_switcher.save();
+            }
+            (KeyCode::Char('s'), modifier)
+                if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT =>
+            {
+                self.doc_switcher.save_all();
             }
 
             // (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
             //     self.show_banner = !self.show_banner;
             // }
 
+            // extend block selection
+            (
+                KeyCode::Up
+                | KeyCode::Down
+                | KeyCode::Left
+                | KeyCode::Right
+                | KeyCode::End
+                | KeyCode::Home,
+                modifier,
+            ) if modifier == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                self.select_block(key.code, block);
+            }
+
+            // move up / down, shift extends the selection
+            (KeyCode::Up | KeyCode::Down, modifier)
+                if modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT =>
+            {
+                let rows = if key.code == KeyCode::Up { -1 } else { 1 };
+                self.move_vertically(rows, modifier == KeyModifiers::SHIFT, columns);
+            }
+
+            // extend selection
+            (
+                KeyCode::Left | KeyCode::Right | KeyCode::End | KeyCode::Home,
+                KeyModifiers::SHIFT,
+            ) => {
+                self.move_cursor(key.code, true);
+            }
+
+            // page up / down, half a page with alt
+            (KeyCode::PageUp | KeyCode::PageDown, modifier)
+                if modifier == KeyModifiers::NONE || modifier == KeyModifiers::SHIFT =>
+            {
+                self.page(key.code, 1, modifier == KeyModifiers::SHIFT, columns);
+            }
+            (KeyCode::PageUp | KeyCode::PageDown, modifier)
+                if modifier.contains(KeyModifiers::ALT) =>
+            {
+                self.page(key.code, 2, modifier.contains(KeyModifiers::SHIFT), columns);
+            }
+
+            // scroll a line, the cursors stay where they are as long as they are on the screen
+            (KeyCode::Up, KeyModifiers::CONTROL) => self.scroll_by(-1),
+            (KeyCode::Down, KeyModifiers::CONTROL) => self.scroll_by(1),
+
+            // the line of the cursor to the middle of the screen
+            (KeyCode::Char('l'), KeyModifiers::CONTROL) => self.center(),
+
+            // start / end of the document
+            (KeyCode::Home | KeyCode::End, modifier)
+                if modifier.contains(KeyModifiers::CONTROL) =>
+            {
+                let extend = modifier.contains(KeyModifiers::SHIFT);
+                self.move_heads(extend, |doc_switcher, _| {
+                    document_edge(doc_switcher, key.code)
+                });
+            }
+
+            // word motions: words with ctrl, subwords with alt, WORDs with alt+b / alt+f
+            (KeyCode::Left | KeyCode::Right, modifier)
+                if modifier == KeyModifiers::CONTROL
+                    || modifier == KeyModifiers::CONTROL | KeyModifiers::SHIFT =>
+            {
+                let extend = modifier.contains(KeyModifiers::SHIFT);
+                self.move_word(WordKind::Word, key.code == KeyCode::Right, extend);
+            }
+            (KeyCode::Left | KeyCode::Right, KeyModifiers::ALT) => {
+                self.move_word(WordKind::Subword, key.code == KeyCode::Right, false);
+            }
+            (KeyCode::Char(c @ ('b' | 'f' | 'B' | 'F')), modifier)
+                if modifier == KeyModifiers::ALT
+                    || modifier == KeyModifiers::ALT | KeyModifiers::SHIFT =>
+            {
+                let extend = c.is_uppercase() || modifier.contains(KeyModifiers::SHIFT);
+                self.move_word(WordKind::BigWord, c.eq_ignore_ascii_case(&'f'), extend);
+            }
+
+            // add a cursor on the line above / below
+            (KeyCode::Up, modifier) if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.add_cursor(KeyCode::Up);
+            }
+            (KeyCode::Down, modifier) if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.add_cursor(KeyCode::Down);
+            }
+
+            // select the word under the cursor, then add its next occurrence
+            (KeyCode::Char('d'), KeyModifiers::CONTROL) => self.select_next(),
+
+            // back to a single cursor
+            (KeyCode::Esc, _) => self.reset_selection(),
+
             // move cursor
             (KeyCode::Up, _)
             | (KeyCode::Down, _)
             | (KeyCode::Left, KeyModifiers::NONE)
             | (KeyCode::Right, KeyModifiers::NONE)
-            | (KeyCode::PageUp, _)
-            | (KeyCode::PageDown, _)
             | (KeyCode::End, _)
-            | (KeyCode::Home, _) => self.move_cursor(key.code),
+            | (KeyCode::Home, _) => {
+                self.move_cursor(key.code, false);
+            }
+
+            // switch buffer
+            (KeyCode::Left, modifier) if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.doc_switcher.prev();
+                self.reset_selection();
+            }
+
+            (KeyCode::Right, modifier) if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT => {
+                self.doc_switcher.next();
+                self.reset_selection();
+            }
+
+            // undo / redo
+            (KeyCode::Char('z'), KeyModifiers::CONTROL) => self.undo(),
+            (KeyCode::Char('y'), KeyModifiers::CONTROL) => self.redo(),
+
+            // command line
+            (KeyCode::Char('p'), KeyModifiers::CONTROL) => {
+                self.command = Some(String::new());
+                self.doc_switcher.prompt(":".to_string());
+            }
+
+            // registers
+            (KeyCode::Char('r'), KeyModifiers::CONTROL) => {
+                self.awaiting_register = true;
+                self.doc_switcher.prompt("register: ".to_string());
+            }
+            (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
+                self.copy();
+            }
+            (KeyCode::Char('x'), KeyModifiers::CONTROL) => self.cut(),
+            (KeyCode::Char('v'), KeyModifiers::CONTROL) => self.paste(),
+
+            // edit the bytes of a binary document
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) if self.is_binary() => {
+                self.type_hex(c, low_nibble);
+            }
+            (KeyCode::Insert, KeyModifiers::NONE) if self.is_binary() => {
+                self.edit(|doc, cursor| doc.insert_bytes(cursor, &[0]));
+            }
+            (KeyCode::Tab | KeyCode::Enter, _) if self.is_binary() => {}
+
+            // edit document
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                self.edit(|doc, cursor| doc.insert_char(cursor, c));
+            }
+            (KeyCode::Tab, KeyModifiers::NONE) => {
+                self.edit(|doc, cursor| doc.insert_indent(cursor));
+            }
+            (KeyCode::Enter, KeyModifiers::NONE) => {
+                self.edit(|doc, cursor| doc.insert_newline(cursor));
+            }
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                self.delete(|doc, cursor| doc.delete_backward(cursor));
+            }
+            (KeyCode::Delete, KeyModifiers::NONE) => {
+                self.delete(|doc, cursor| doc.delete_forward(cursor));
+            }
+
+            // delete a word, a terminal sends ctrl+backspace as ctrl+h
+            (KeyCode::Backspace, KeyModifiers::CONTROL | KeyModifiers::ALT)
+            | (KeyCode::Char('h'), KeyModifiers::CONTROL) => {
+                self.delete(|doc, cursor| doc.delete_word(cursor, WordKind::Word, false));
+            }
+            (KeyCode::Delete, KeyModifiers::CONTROL) | (KeyCode::Char('d'), KeyModifiers::ALT) => {
+                self.delete(|doc, cursor| doc.delete_word(cursor, WordKind::Word, true));
+            }
+
+            _ => {}
+        };
+        self.scroll();
+    }
+
+    /// quit editor, ask first if any document has unsaved changes.
+    fn quit(&mut self) {
+        match self.doc_switcher.modified_count() {
+            0 => self.running = false,
+            count => {
+                self.pending = Some(Pending::Quit);
+                self.doc_switcher.prompt(format!(
+                    "{} buffers modified, [s]ave all / [d]iscard / [c]ancel",
+                    count
+                ));
+            }
+        }
+    }
+
+    /// close current document, ask first if it has unsaved changes.
+    fn close(&mut self) {
+        let modified = match self.doc_switcher.current() {
+            None => return,
+            Some(doc) => doc.is_modified(),
+        };
+
+        if !modified {
+            self.close_current();
+            return;
+        }
+
+        self.pending = Some(Pending::Close);
+        let name = self.doc_switcher.name();
+        self.doc_switcher.prompt(format!(
+            "\"{}\" modified, [s]ave / [d]iscard / [c]ancel",
+            name
+        ));
+    }
+
+    fn close_current(&mut self) {
+        self.doc_switcher.remove_current().remove_swap();
+        self.reset_selection();
+    }
 
-            (KeyCode::Left, modifier) => {
-                // switch buffer
-                if modifier == KeyModifiers::CONTROL | KeyModifiers::ALT {
-                    self.doc_switcher.prev();
-                    self.move_cursor(KeyCode::Null);
+    /// type into the command line, `Enter` runs the command and `Esc` leaves.
+    fn on_command_key(&mut self, key: KeyEvent) {
+        let command = match self.command.as_mut() {
+            Some(command) => command,
+            None => return,
+        };
+
+        match (key.code, key.modifiers) {
+            (KeyCode::Esc, _) => self.command = None,
+            (KeyCode::Enter, _) => {
+                let line = self.command.take().unwrap_or_default();
+                self.doc_switcher.clear_message();
+                self.run_command(&line);
+                return;
+            }
+            (KeyCode::Backspace, _) => {
+                command.pop();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE | KeyModifiers::SHIFT) => command.push(c),
+            _ => {}
+        }
+
+        match &self.command {
+            Some(command) => self.doc_switcher.prompt(format!(":{}", command)),
+            None => self.doc_switcher.clear_message(),
+        }
+    }
+
+    fn run_command(&mut self, line: &str) {
+        let command = match Command::parse(line) {
+            Ok(command) => command,
+            Err(err) => {
+                self.doc_switcher.message(err);
+                return;
+            }
+        };
+
+        let view = self.view_state();
+        let doc = match self.doc_switcher.current_mut() {
+            Some(doc) => doc,
+            None => return,
+        };
+        match command {
+            Command::LineEnding(_) if !doc.is_loaded() => {
+                let message = format!("\"{}\" can't be edited before it's loaded", doc.name());
+                self.doc_switcher.message(message);
+            }
+            Command::LineEnding(_) if doc.is_read_only() => {
+                let message = format!("\"{}\" is read-only", doc.name());
+                self.doc_switcher.message(message);
+            }
+            Command::LineEnding(_) if doc.is_binary() => {
+                self.doc_switcher
+                    .message("a binary file has no line endings".to_string());
+            }
+            Command::LineEnding(line_ending) => {
+                doc.set_line_ending(view, line_ending);
+                self.doc_switcher
+                    .message(format!("line endings converted to {}", line_ending));
+            }
+            Command::Find(text) => {
+                // a binary document is searched for hex bytes.
+                let pattern = match doc.is_binary() {
+                    true => {
+                        hex::parse(&text).map(|bytes| bytes.into_iter().map(char::from).collect())
+                    }
+                    false => Some(text),
+                };
+                let found = pattern
+                    .and_then(|pattern| doc.find_next(&pattern, self.selection.primary().end()));
+                match found {
+                    Some(range) => self.selection = Selection::single(range),
+                    None => self.doc_switcher.message("not found".to_string()),
                 }
             }
+            Command::Encoding(_) if doc.is_modified() => {
+                self.doc_switcher
+                    .message("save or undo the changes before reopening".to_string());
+            }
+            Command::Recover => self.recover(),
+            Command::Reload => {
+                let index = self.doc_switcher.index();
+                self.reload(index);
+            }
+            Command::Encoding(encoding) => {
+                let message = match doc.reopen(encoding) {
+                    Ok(_) => format!("reopened as {}", encoding.name()),
+                    Err(err) => format!("reopen failed: {}", err),
+                };
+                self.doc_switcher.message(message);
+                self.move_cursor(KeyCode::Null, false);
+            }
+        }
+    }
 
-            (KeyCode::Right, key_modifier) => {
-                // switch buffer
-                if key_modifier == KeyModifiers::CONTROL | KeyModifiers::ALT {
-                    self.doc_switcher.next();
-                    self.move_cursor(KeyCode::Null);
+    /// answer the save / discard / cancel prompt.
+    fn on_prompt(&mut self, pending: Pending, key: KeyEvent) {
+        let save = match pending {
+            Pending::Quit => DocumentSwitcher::save_all,
+            Pending::Close => DocumentSwitcher::save,
+            Pending::Changed(index) => return self.on_changed_prompt(index, key),
+            Pending::Recover(index) => return self.on_recover_prompt(index, key),
+        };
+        let proceed = match key.code {
+            KeyCode::Char('s') => save(&mut self.doc_switcher),
+            KeyCode::Char('d') => {
+                self.doc_switcher.clear_message();
+                true
+            }
+            KeyCode::Char('c') | KeyCode::Esc => {
+                self.doc_switcher.clear_message();
+                false
+            }
+            // keep waiting for an answer
+            _ => return,
+        };
+
+        self.pending = None;
+        if !proceed {
+            return;
+        }
+
+        match pending {
+            Pending::Quit => self.running = false,
+            Pending::Close => self.close_current(),
+            Pending::Changed(_) | Pending::Recover(_) => {}
+        }
+    }
+
+    /// answer the keep mine / take theirs / diff prompt of a document changed on disk.
+    fn on_changed_prompt(&mut self, index: usize, key: KeyEvent) {
+        match key.code {
+            KeyCode::Char('k') | KeyCode::Esc => self.doc_switcher.clear_message(),
+            KeyCode::Char('t') => self.reload(index),
+            // a binary file has no lines to diff.
+            KeyCode::Char('d')
+                if !self
+                    .doc_switcher
+                    .get(index)
+                    .is_some_and(Document::is_binary) =>
+            {
+                self.diff(index)
+            }
+            // keep waiting for an answer
+            _ => return,
+        }
+        self.pending = None;
+    }
+
+    /// look for files changed on disk, a clean document is reloaded and the user is asked about a modified one.
+    ///
+    /// while a prompt waits for an answer nothing is checked, the changes are found after it.
+    fn check_disk(&mut self) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        for index in 0..self.doc_switcher.size() {
+            let doc = match self.doc_switcher.get_mut(index) {
+                Some(doc) => doc,
+                None => return,
+            };
+            let (name, modified, binary) = (doc.name(), doc.is_modified(), doc.is_binary());
+            match doc.check_disk() {
+                DiskChange::Unchanged => {}
+                DiskChange::Deleted => {
+                    self.doc_switcher
+                        .message(format!("\"{}\" was deleted on disk", name));
                 }
+                DiskChange::Changed(_) if modified => {
+                    self.pending = Some(Pending::Changed(index));
+                    self.doc_switcher.prompt(format!(
+                        "\"{}\" changed on disk, [k]eep mine / [t]ake theirs{}",
+                        name,
+                        if binary { "" } else { " / [d]iff" }
+                    ));
+                    return;
+                }
+                DiskChange::Changed(_) => self.reload(index),
             }
+        }
+    }
 
-            _ => {}
+    /// read the document at `index` again, the cursors of the current one stay where they are.
+    fn reload(&mut self, index: usize) {
+        let current = index == self.doc_switcher.index();
+        let view = match current {
+            true => self.view_state(),
+            false => ViewState::default(),
+        };
+        let doc = match self.doc_switcher.get_mut(index) {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let message = match doc.reload(view) {
+            Ok(_) => format!("\"{}\" reloaded", doc.name()),
+            Err(err) => format!("\"{}\" reload failed: {}", doc.name(), err),
+        };
+        self.doc_switcher.message(message);
+        if current {
+            self.move_cursor(KeyCode::Null, true);
+            self.scroll();
+        }
+    }
+
+    /// open the changes from the file on disk to the document at `index` as a new document.
+    fn diff(&mut self, index: usize) {
+        let doc = match self.doc_switcher.get(index) {
+            Some(doc) => doc,
+            None => return,
+        };
+        let name = doc.name();
+        let encoding = (!doc.is_binary()).then_some(doc.encoding());
+        let disk = match Document::read(&name, encoding) {
+            Ok(disk) => disk,
+            Err(err) => {
+                self.doc_switcher
+                    .message(format!("\"{}\" diff failed: {}", name, err));
+                return;
+            }
+        };
+
+        let text = diff::unified(
+            &disk.content.to_string(),
+            &doc.content.to_string(),
+            &format!("{} (disk)", name),
+            &format!("{} (buffer)", name),
+        );
+        let message = match text.is_empty() {
+            true => format!("\"{}\" is the same as on disk", name),
+            false => format!("\"{}\" changed on disk, :reload takes theirs", name),
+        };
+        self.open_diff(&name, text);
+        self.doc_switcher.message(message);
+    }
+
+    /// show the diff `text` of the document `name` as a new document, nothing is shown when it's empty.
+    fn open_diff(&mut self, name: &str, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.doc_switcher.add(Document::scratch(
+            Rope::from(text),
+            &format!("{} [diff]", name),
+        ));
+        self.doc_switcher.select(self.doc_switcher.size() - 1);
+        self.reset_selection();
+    }
+
+    /// ask about the first document from `from` on that has the swap file of a crashed session.
+    fn ask_recovery(&mut self, from: usize) {
+        for index in from..self.doc_switcher.size() {
+            let name = match self.doc_switcher.get(index) {
+                Some(doc) if doc.recovery().is_some() => doc.name(),
+                _ => continue,
+            };
+
+            self.doc_switcher.select(index);
+            self.reset_selection();
+            self.pending = Some(Pending::Recover(index));
+            self.doc_switcher.prompt(format!(
+                "\"{}\" has unsaved changes of a crashed session, [r]ecover / [v]iew diff / [d]elete",
+                name
+            ));
+            return;
+        }
+    }
+
+    /// answer the recover / view diff / delete prompt, then ask about the next document.
+    ///
+    /// `Esc` keeps the swap file for later, see [`Command::Recover`].
+    fn on_recover_prompt(&mut self, index: usize, key: KeyEvent) {
+        let (name, recovery) = match self.doc_switcher.get(index) {
+            Some(doc) => (doc.name(), doc.recovery().unwrap_or_default().to_string()),
+            None => return,
         };
+
+        match key.code {
+            KeyCode::Char('r') => self.recover(),
+            KeyCode::Char('v') => {
+                let text = match self.doc_switcher.get(index) {
+                    Some(doc) => diff::unified(
+                        &doc.content.to_string(),
+                        &recovery,
+                        &name,
+                        &format!("{} (swap)", name),
+                    ),
+                    None => return,
+                };
+                self.open_diff(&name, text);
+                self.doc_switcher
+                    .message(format!("\"{}\" swap file kept, :recover takes it", name));
+            }
+            KeyCode::Char('d') => {
+                if let Some(doc) = self.doc_switcher.get_mut(index) {
+                    doc.discard_recovery();
+                }
+                self.doc_switcher
+                    .message(format!("\"{}\" swap file deleted", name));
+            }
+            KeyCode::Esc => self.doc_switcher.clear_message(),
+            // keep waiting for an answer
+            _ => return,
+        }
+
+        self.pending = None;
+        self.ask_recovery(index + 1);
+    }
+
+    /// take the content of the swap file into the current document.
+    fn recover(&mut self) {
+        let view = self.view_state();
+        let doc = match self.doc_switcher.current_mut() {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let message = match doc.recover(view) {
+            true => format!("\"{}\" recovered, save to keep it", doc.name()),
+            false => "no swap file to recover".to_string(),
+        };
+        self.doc_switcher.message(message);
+        self.move_cursor(KeyCode::Null, true);
+        self.scroll();
+    }
+
+    /// the terminal pasted `text`, insert it as one undo step.
+    ///
+    /// terminals may send line breaks as `\r`, they become `\n`.
+    fn on_paste(&mut self, text: String) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.block = None;
+        self.paste_text(&text);
         self.scroll();
     }
 
     /// on tick event
     fn on_tick(&mut self) {
+        self.doc_switcher.poll_loaders();
+        self.check_disk();
+        self.doc_switcher.write_swaps();
+        if self
+            .clipboard
+            .as_ref()
+            .is_some_and(clipboard::Read::is_expired)
+        {
+            self.clipboard = None;
+            self.paste_clipboard(None);
+            self.scroll();
+        }
         self.refresh_screen().unwrap();
     }
 
-    /// move cursor
-    fn move_cursor(&mut self, key_code: KeyCode) {
-        let Position { mut x, mut y } = self.cursor;
-        let offset = self.offset;
-        let (screen_width, screen_height) = screen::size().unwrap();
-
-        // row
-        let rope_slice = self.doc_switcher.current_doc_row(y);
-        let doc_height = self.doc_switcher.current_doc_height();
-        let doc_width = rope_slice.len_word_boundary();
-        let row_width = rope_slice.len_chars();
-
-        match key_code {
-            KeyCode::Left => {
-                if x > 0 {
-                    x = x.saturating_sub(1);
-                } else if y > 0 {
-                    y = y.saturating_sub(1);
-                    x = doc_width
-                }
+    /// apply an edit to the current document at every cursor, then move the cursors to where the edits end.
+    ///
+    /// the edit replaces the selected text.
+    fn edit<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Document, Position) -> Position,
+    {
+        self.edit_ranges(|doc, range| {
+            let cursor = doc.delete_range(range);
+            f(doc, cursor)
+        });
+    }
+
+    /// delete the selected text, or apply `f` at the cursors without a selection.
+    fn delete<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&mut Document, Position) -> Position,
+    {
+        self.edit_ranges(|doc, range| match range.is_empty() {
+            true => f(doc, range.head),
+            false => doc.delete_range(range),
+        });
+    }
+
+    fn edit_ranges<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut Document, Range) -> Position,
+    {
+        let view = self.view_state();
+        match self.doc_switcher.current_mut() {
+            Some(doc) if !doc.is_loaded() => {
+                let message = format!("\"{}\" can't be edited before it's loaded", doc.name());
+                self.doc_switcher.message(message);
+            }
+            Some(doc) if doc.is_read_only() => {
+                let message = format!("\"{}\" is read-only", doc.name());
+                self.doc_switcher.message(message);
             }
-            KeyCode::Right => {
-                // 正常向右移动一位
-                if x < doc_width {
-                    x = x.saturating_add(1);
-                    // 换到下一行
-                } else if y < doc_height {
-                    y = y.saturating_add(1);
-                    x = 0;
+            Some(doc) => self.selection = doc.edit(view, f),
+            None => {}
+        }
+    }
+
+    /// copy the selected text to the register, returns whether anything was copied.
+    ///
+    /// the text of multiple ranges is joined by newlines.
+    fn copy(&mut self) -> bool {
+        let register = std::mem::replace(&mut self.register, DEFAULT_REGISTER);
+        let doc = match self.doc_switcher.current() {
+            Some(doc) if !self.selection.is_empty() => doc,
+            _ => return false,
+        };
+
+        let text = self
+            .selection
+            .ranges()
+            .iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| doc.copy_text(*range))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if let Err(err) = self.screen.set_clipboard(&text) {
+            self.doc_switcher
+                .message(format!("copy to clipboard fail: {}", err));
+        }
+        self.registers.set(CLIPBOARD_REGISTER, text.clone());
+        self.registers.set(register, text);
+        true
+    }
+
+    /// copy the selected text to the register and delete it.
+    fn cut(&mut self) {
+        if self.copy() {
+            self.edit(|_, cursor| cursor);
+        }
+    }
+
+    /// replace the selected text with the content of the register, at every cursor.
+    ///
+    /// the clipboard register is read from the terminal, the paste happens when it answers.
+    fn paste(&mut self) {
+        let register = std::mem::replace(&mut self.register, DEFAULT_REGISTER);
+        if register == CLIPBOARD_REGISTER && self.screen.request_clipboard().is_ok() {
+            self.clipboard = Some(clipboard::Read::default());
+            return;
+        }
+
+        self.paste_register(register);
+    }
+
+    /// paste the text read from the clipboard, fall back to the clipboard register
+    /// when the terminal didn't send it.
+    fn paste_clipboard(&mut self, text: Option<String>) {
+        match text {
+            Some(text) => self.paste_text(&text),
+            None => self.paste_register(CLIPBOARD_REGISTER),
+        }
+    }
+
+    fn paste_register(&mut self, register: char) {
+        match self.registers.get(register).map(str::to_string) {
+            Some(text) => self.paste_text(&text),
+            None => self
+                .doc_switcher
+                .message(format!("register {} is empty", register)),
+        }
+    }
+
+    /// insert `text` at every cursor as an undo step of its own.
+    ///
+    /// when the text has a line for every cursor, e.g. it's copied from a block, each cursor gets its own line.
+    ///
+    /// a binary document takes hex bytes, e.g. `de ad be ef`.
+    fn paste_text(&mut self, text: &str) {
+        let bytes = match self.is_binary() {
+            true => match hex::parse(text) {
+                Some(bytes) => Some(bytes),
+                None => {
+                    self.doc_switcher
+                        .message("only hex bytes can be pasted into a binary file".to_string());
+                    return;
                 }
+            },
+            false => None,
+        };
+        if let Some(doc) = self.doc_switcher.current_mut() {
+            doc.close_group();
+        }
+
+        if let Some(bytes) = bytes {
+            self.edit(|doc, cursor| doc.insert_bytes(cursor, &bytes));
+        } else if self.selection.len() > 1 && text.lines().count() == self.selection.len() {
+            // the edit runs from the last cursor to the first.
+            let mut lines = text.lines().rev();
+            self.edit(|doc, cursor| doc.insert(cursor, lines.next().unwrap_or_default()));
+        } else {
+            self.edit(|doc, cursor| doc.insert(cursor, text));
+        }
+
+        if let Some(doc) = self.doc_switcher.current_mut() {
+            doc.close_group();
+        }
+    }
+
+    /// type a hex digit into a binary document, two digits overwrite the byte at every cursor.
+    fn type_hex(&mut self, c: char, low_nibble: bool) {
+        let digit = match c.to_digit(16) {
+            Some(digit) => digit as u8,
+            None => {
+                self.doc_switcher.message(format!("not a hex digit: {}", c));
+                return;
             }
-            KeyCode::Up => {
-                y = y.saturating_sub(1);
+        };
+
+        self.edit_ranges(|doc, range| doc.overwrite_nibble(range.head, low_nibble, digit));
+        self.low_nibble = !low_nibble;
+    }
+
+    fn is_binary(&self) -> bool {
+        self.doc_switcher.current().is_some_and(Document::is_binary)
+    }
+
+    /// add a cursor on the line above / below the primary cursor.
+    fn add_cursor(&mut self, key_code: KeyCode) {
+        let cursor = self.selection.cursor();
+        let y = match key_code {
+            KeyCode::Up if cursor.y > 0 => cursor.y - 1,
+            KeyCode::Down if cursor.y + 1 < self.doc_switcher.current_doc_height() => cursor.y + 1,
+            _ => return,
+        };
+
+        // the new cursor is in the same display column.
+        let column = self.doc_switcher.current_doc_column(cursor);
+        let x = self.doc_switcher.current_doc_grapheme_at_column(y, column);
+        self.selection.push(Range::point(Position { x, y }));
+    }
+
+    /// select the word under the primary cursor, when it's already selected
+    /// add a cursor at the next occurrence of the selected text.
+    fn select_next(&mut self) {
+        let doc = match self.doc_switcher.current() {
+            Some(doc) => doc,
+            None => return,
+        };
+
+        let primary = self.selection.primary();
+        if primary.is_empty() {
+            if let Some(word) = doc.word_at(primary.head) {
+                self.selection.set_primary(word);
             }
-            KeyCode::Down => {
-                if y < doc_height {
-                    y = y.saturating_add(1);
-                }
+            return;
+        }
+
+        let next = doc
+            .find_next(&doc.text(primary), primary.end())
+            .filter(|next| !self.selection.ranges().contains(next));
+        match next {
+            Some(next) => self.selection.push(next),
+            None => self.doc_switcher.message("no more matches".to_string()),
+        }
+    }
+
+    /// start a block at the primary cursor, or move the head of `block`, then select it.
+    fn select_block(&mut self, key_code: KeyCode, block: Option<Block>) {
+        let mut block = block.unwrap_or_else(|| {
+            let cursor = self.selection.cursor();
+            let line = self
+                .doc_switcher
+                .current_doc_row_to_line(cursor.y, self.offset.x);
+            Block::point(Position {
+                x: line.get_offset(cursor.x),
+                y: cursor.y,
+            })
+        });
+
+        // move by graphemes inside of the line and by columns past its end.
+        let head = &mut block.head;
+        let line = self
+            .doc_switcher
+            .current_doc_row_to_line(head.y, self.offset.x);
+        match (key_code, line.grapheme_at(head.x)) {
+            (KeyCode::Up, _) => head.y = head.y.saturating_sub(1),
+            (KeyCode::Down, _) if head.y + 1 < self.doc_switcher.current_doc_height() => {
+                head.y += 1
             }
-            KeyCode::Home => x = 0,
-            KeyCode::End => x = doc_width,
-            KeyCode::PageUp => {}
-            KeyCode::PageDown => {}
+            (KeyCode::Left, Some(x)) => head.x = line.get_offset(x.saturating_sub(1)),
+            (KeyCode::Left, None) => head.x = head.x.saturating_sub(1),
+            (KeyCode::Right, Some(x)) => head.x = line.get_offset(x + 1),
+            (KeyCode::Right, None) => head.x += 1,
+            (KeyCode::Home, _) => head.x = 0,
+            (KeyCode::End, _) => head.x = line.get_offset(line.len()),
             _ => {}
         }
 
-        let current_row_width = self.doc_switcher.current_doc_row(y).len_word_boundary();
-        if x > current_row_width {
-            x = current_row_width;
+        let (doc_switcher, start) = (&self.doc_switcher, self.offset.x);
+        self.selection = block.selection(|y| doc_switcher.current_doc_row_to_line(y, start));
+        self.block = Some(block);
+    }
+
+    /// drop every cursor but the primary one and clear the selection.
+    fn reset_selection(&mut self) {
+        self.selection = Selection::point(self.selection.cursor());
+        self.move_cursor(KeyCode::Null, false);
+    }
+
+    /// undo the last edit of the current document and restore the view it was made in.
+    fn undo(&mut self) {
+        match self.doc_switcher.current_mut().and_then(|doc| doc.undo()) {
+            Some(view) => self.restore_view(view),
+            None => self
+                .doc_switcher
+                .message("already at oldest change".to_string()),
+        }
+    }
+
+    /// redo the last undone edit of the current document.
+    fn redo(&mut self) {
+        match self.doc_switcher.current_mut().and_then(|doc| doc.redo()) {
+            Some(view) => self.restore_view(view),
+            None => self
+                .doc_switcher
+                .message("already at newest change".to_string()),
         }
+    }
 
-        // 索引是从0开始的,所以减1,
-        if y > doc_height - 1 {
-            y = doc_height - 1
+    fn view_state(&self) -> ViewState {
+        ViewState {
+            selection: self.selection.clone(),
+            offset: self.offset,
         }
+    }
+
+    fn restore_view(&mut self, view: ViewState) {
+        self.selection = view.selection;
+        self.offset = view.offset;
+        self.move_cursor(KeyCode::Null, true);
+    }
+
+    /// move every cursor, `extend` keeps the anchors so the selections grow with the cursors.
+    fn move_cursor(&mut self, key_code: KeyCode, extend: bool) {
+        self.move_heads(extend, |doc_switcher, head| {
+            moved(doc_switcher, head, key_code)
+        });
+    }
+
+    /// move every cursor to where `f` says, the position is clamped to the document.
+    fn move_heads<F>(&mut self, extend: bool, f: F)
+    where
+        F: Fn(&DocumentSwitcher, Position) -> Position,
+    {
+        moved_heads(&self.doc_switcher, &mut self.selection, extend, f);
+    }
+
+    /// move every cursor to the end of the next word (or the start of the previous one), see [`Document::word_motion`].
+    fn move_word(&mut self, kind: WordKind, forward: bool, extend: bool) {
+        self.move_heads(extend, |doc_switcher, head| match doc_switcher.current() {
+            Some(doc) => doc.word_motion(head, kind, forward),
+            None => head,
+        });
+    }
+
+    /// the lines of the document on the screen, the status line and message bar take the rest.
+    fn viewport_height(&self) -> usize {
+        let (_, height) = screen::size().unwrap();
+        (height as usize).saturating_sub(self.doc_switcher.get_bottom_height())
+    }
+
+    /// move every cursor `rows` lines down (up when it's negative), see [`moved_vertically`].
+    fn move_vertically(&mut self, rows: isize, extend: bool, columns: Vec<usize>) {
+        self.columns = moved_vertically(
+            &self.doc_switcher,
+            &mut self.selection,
+            rows,
+            extend,
+            columns,
+        );
+    }
+
+    /// move the cursors and the screen a page (or `1 / fraction` of it) up or down.
+    fn page(&mut self, key_code: KeyCode, fraction: usize, extend: bool, columns: Vec<usize>) {
+        let height = self.viewport_height();
+        self.columns = paged(
+            &self.doc_switcher,
+            &mut self.selection,
+            &mut self.offset,
+            height,
+            page_rows(key_code, fraction, height),
+            extend,
+            columns,
+        );
+    }
+
+    /// scroll the screen by `rows` lines, see [`scrolled`].
+    fn scroll_by(&mut self, rows: isize) {
+        let height = self.viewport_height();
+        scrolled(
+            &self.doc_switcher,
+            &mut self.selection,
+            &mut self.offset,
+            height,
+            rows,
+        );
+    }
 
-        self.cursor = Position { x, y };
+    /// scroll so the line of the cursor is in the middle of the screen.
+    fn center(&mut self) {
+        self.offset.y = centered(self.selection.cursor().y, self.viewport_height());
     }
 
     fn scroll(&mut self) {
-        let Position { x, y } = self.cursor;
-        let screen_size = screen::size().unwrap();
-        let h = (screen_size.1 as usize) - self.doc_switcher.get_bottom_height();
-        let w = screen_size.0 as usize;
-        let line = self.doc_switcher.current_doc_row_to_line(y);
+        let Position { x, y } = self.selection.cursor();
+        let h = self.viewport_height();
+        let w = screen::size().unwrap().0 as usize;
 
         let mut offset = self.offset;
         if y < offset.y {
@@ -283,11 +1210,17 @@ impl App {
             offset.y = y.saturating_sub(h).saturating_add(1);
         };
 
-        /// todo  offset.x 有问题
+        let line = self.doc_switcher.current_doc_row_to_line(y, offset.x);
         if x < offset.x {
             offset.x = x;
-        } else if line.get_offset(x) >= line.get_offset(offset.x).saturating_add(w) {
-            offset.x = offset.x.saturating_add(1);
+        } else if line.get_offset(x) >= line.origin(offset.x).saturating_add(w) {
+            // 光标在屏幕右边之外, 滚动到光标是最后一列, 只转换光标左边一屏的部分
+            let start = x.saturating_sub(w);
+            let line = self.doc_switcher.current_doc_row_to_line(y, start);
+            let right = line.get_offset(x) + line.grapheme_width(x).max(1);
+            offset.x = (start..x)
+                .find(|x| right <= line.origin(*x).saturating_add(w))
+                .unwrap_or(x);
         }
 
         self.offset = offset;
@@ -298,13 +1231,17 @@ impl App {
     }
 
     fn new_ctx(&self) -> AppCtx {
-        let doc_size = self.doc_switcher.current_doc_size(self.cursor.y);
+        let cursor = self.selection.cursor();
+        let doc_size = self.doc_switcher.current_doc_size(cursor.y);
         let bottom_height = self.doc_switcher.get_bottom_height();
 
         AppCtx {
-            current_line: self.doc_switcher.current_doc_row_to_line(self.cursor.y),
+            current_line: self
+                .doc_switcher
+                .current_doc_row_to_line(cursor.y, self.offset.x),
             offset: self.offset,
-            cursor: self.cursor,
+            cursor,
+            selection: self.selection.clone(),
             screen_size: screen::size().unwrap(),
             doc_size,
             bottom_height,
@@ -312,6 +1249,195 @@ impl App {
     }
 }
 
+/// move every cursor to where `f` says, the position is clamped to the document.
+///
+/// `extend` keeps the anchors so the selections grow with the cursors.
+fn moved_heads<F>(doc_switcher: &DocumentSwitcher, selection: &mut Selection, extend: bool, f: F)
+where
+    F: Fn(&DocumentSwitcher, Position) -> Position,
+{
+    selection.transform(|range| {
+        let head = moved(doc_switcher, f(doc_switcher, range.head), KeyCode::Null);
+        match extend {
+            true => Range::new(range.anchor, head),
+            false => Range::point(head),
+        }
+    });
+}
+
+/// where ctrl+home / ctrl+end go: the start or the end of the document.
+fn document_edge(doc_switcher: &DocumentSwitcher, key_code: KeyCode) -> Position {
+    match key_code {
+        KeyCode::Home => Position::default(),
+        _ => Position {
+            x: usize::MAX,
+            y: doc_switcher.current_doc_height().saturating_sub(1),
+        },
+    }
+}
+
+/// how many lines a page (or `1 / fraction` of it) of a screen `height` lines high is,
+/// negative for page up.
+fn page_rows(key_code: KeyCode, fraction: usize, height: usize) -> isize {
+    let rows = (height / fraction).max(1) as isize;
+    match key_code {
+        KeyCode::PageUp => -rows,
+        _ => rows,
+    }
+}
+
+/// move the cursors and the screen `rows` lines, returns the columns for the next move.
+///
+/// see [`moved_vertically`] and [`scrolled`].
+fn paged(
+    doc_switcher: &DocumentSwitcher,
+    selection: &mut Selection,
+    offset: &mut Position,
+    height: usize,
+    rows: isize,
+    extend: bool,
+    columns: Vec<usize>,
+) -> Vec<usize> {
+    let columns = moved_vertically(doc_switcher, selection, rows, extend, columns);
+    scrolled(doc_switcher, selection, offset, height, rows);
+    columns
+}
+
+/// scroll a screen `height` lines high by `rows` lines, down when it's positive.
+/// the last line stays at the bottom.
+///
+/// the cursors that left the screen move back to its edge,
+/// a selection keeps its anchor and grows, a cursor without one stays a cursor.
+fn scrolled(
+    doc_switcher: &DocumentSwitcher,
+    selection: &mut Selection,
+    offset: &mut Position,
+    height: usize,
+    rows: isize,
+) {
+    let height = height.max(1);
+    let doc_height = doc_switcher.current_doc_height();
+    offset.y = offset
+        .y
+        .saturating_add_signed(rows)
+        .min(doc_height.saturating_sub(height));
+
+    let (top, bottom) = (offset.y, offset.y + height - 1);
+    let visible = |range: &Range| (top..=bottom).contains(&range.head.y);
+    if selection.ranges().iter().all(visible) {
+        return;
+    }
+    selection.transform(|range| {
+        let head = Position {
+            x: range.head.x,
+            y: range.head.y.clamp(top, bottom),
+        };
+        let head = moved(doc_switcher, head, KeyCode::Null);
+        match range.is_empty() {
+            true => Range::point(head),
+            false => Range::new(range.anchor, head),
+        }
+    });
+}
+
+/// the top line of a screen `height` lines high that has line `y` in the middle.
+fn centered(y: usize, height: usize) -> usize {
+    y.saturating_sub(height / 2)
+}
+
+/// move every cursor `rows` lines down (up when it's negative) in the display column it started in,
+/// returns the columns for the next move.
+///
+/// `columns` are the ones of the moves right before, so a cursor that went through a short line
+/// or a wide glyph gets back to its column on the next line that has it.
+/// they are counted from the start of the line, see [`Document::column`].
+fn moved_vertically(
+    doc_switcher: &DocumentSwitcher,
+    selection: &mut Selection,
+    rows: isize,
+    extend: bool,
+    columns: Vec<usize>,
+) -> Vec<usize> {
+    let columns = match columns.len() == selection.len() {
+        true => columns,
+        false => selection
+            .ranges()
+            .iter()
+            .map(|range| doc_switcher.current_doc_column(range.head))
+            .collect(),
+    };
+
+    let last = doc_switcher.current_doc_height().saturating_sub(1);
+    let mut column = columns.iter();
+    selection.transform(|range| {
+        let y = range.head.y.saturating_add_signed(rows).min(last);
+        let column = column.next().copied().unwrap_or(usize::MAX);
+        let head = Position {
+            x: doc_switcher.current_doc_grapheme_at_column(y, column),
+            y,
+        };
+        match extend {
+            true => Range::new(range.anchor, head),
+            false => Range::point(head),
+        }
+    });
+
+    // cursors that ran into each other start over.
+    match selection.len() == columns.len() {
+        true => columns,
+        false => Vec::new(),
+    }
+}
+
+/// where a cursor at `pos` moves to.
+fn moved(doc_switcher: &DocumentSwitcher, pos: Position, key_code: KeyCode) -> Position {
+    let Position { mut x, mut y } = pos;
+
+    // row
+    let doc_height = doc_switcher.current_doc_height();
+    // 很长的行只数到需要的位置
+    let doc_width = |max| doc_switcher.current_doc_line_len_at_most(pos.y, max);
+
+    match key_code {
+        KeyCode::Left => {
+            if x > 0 {
+                x = x.saturating_sub(1);
+            } else if y > 0 {
+                y = y.saturating_sub(1);
+                x = doc_switcher.current_doc_line_len(y);
+            }
+        }
+        KeyCode::Right => {
+            // 正常向右移动一位
+            if x < doc_width(x + 1) {
+                x = x.saturating_add(1);
+                // 换到下一行
+            } else if y.saturating_add(1) < doc_height {
+                y = y.saturating_add(1);
+                x = 0;
+            }
+        }
+        KeyCode::Up => {
+            y = y.saturating_sub(1);
+        }
+        KeyCode::Down if y.saturating_add(1) < doc_height => {
+            y = y.saturating_add(1);
+        }
+        KeyCode::Home => x = 0,
+        KeyCode::End => x = doc_width(usize::MAX),
+        _ => {}
+    }
+
+    // 索引是从0开始的,所以减1,
+    if y > doc_height.saturating_sub(1) {
+        y = doc_height.saturating_sub(1)
+    }
+
+    x = doc_switcher.current_doc_line_len_at_most(y, x);
+
+    Position { x, y }
+}
+
 fn exit_with_err(err: anyhow::Error) {
     screen::exit().unwrap();
     panic!("{}", err)
@@ -321,4 +1447,249 @@ impl Default for App {
     fn default() -> Self {
         App::new(250)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::KeyCode;
+    use ropey::Rope;
+
+    use crate::app::{
+        centered, document_edge, moved, moved_heads, moved_vertically, page_rows, paged, scrolled,
+    };
+    use crate::render::document::Document;
+    use crate::render::switcher::DocumentSwitcher;
+    use crate::screen::Position;
+    use crate::selection::{Range, Selection};
+
+    /// the cursor position `x`, `y`.
+    fn pos(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    fn switcher(text: &str) -> DocumentSwitcher {
+        let mut doc_switcher = DocumentSwitcher::default();
+        doc_switcher.add(Document::from(Rope::from(text), "test"));
+        doc_switcher
+    }
+
+    #[test]
+    fn test_moved() {
+        let doc_switcher = switcher("ab\nlonger line\nc");
+
+        // left at the start of a line goes to the end of the previous one.
+        assert_eq!(moved(&doc_switcher, pos(0, 1), KeyCode::Left), pos(2, 0));
+        assert_eq!(moved(&doc_switcher, pos(0, 2), KeyCode::Left), pos(11, 1));
+        assert_eq!(moved(&doc_switcher, pos(0, 0), KeyCode::Left), pos(0, 0));
+
+        assert_eq!(moved(&doc_switcher, pos(2, 0), KeyCode::Right), pos(0, 1));
+        assert_eq!(moved(&doc_switcher, pos(1, 2), KeyCode::Right), pos(1, 2));
+        assert_eq!(moved(&doc_switcher, pos(9, 1), KeyCode::Down), pos(1, 2));
+        assert_eq!(moved(&doc_switcher, pos(3, 1), KeyCode::End), pos(11, 1));
+    }
+
+    #[test]
+    fn test_moved_vertically() {
+        let doc_switcher = switcher("你好世界\nab\n\tx\nabcdefgh");
+        let heads = |selection: &Selection| {
+            selection
+                .ranges()
+                .iter()
+                .map(|range| range.head)
+                .collect::<Vec<_>>()
+        };
+
+        // the column after 你好 is 4: past the end of a short line, then in a `\t`.
+        let mut selection = Selection::point(pos(2, 0));
+        let mut columns = Vec::new();
+        let mut passed = Vec::new();
+        for _ in 0..3 {
+            columns = moved_vertically(&doc_switcher, &mut selection, 1, false, columns);
+            passed.push(selection.cursor());
+        }
+        assert_eq!(passed, [pos(2, 1), pos(1, 2), pos(4, 3)]);
+        assert_eq!(columns, [4]);
+        moved_vertically(&doc_switcher, &mut selection, -3, true, columns);
+        assert_eq!(selection.primary(), Range::new(pos(4, 3), pos(2, 0)));
+
+        // column 1 is inside of the `\t` and the wide 你, two cursors keep their own.
+        let mut selection = Selection::point(pos(1, 3));
+        selection.push(Range::point(pos(6, 3)));
+        let mut columns = Vec::new();
+        let mut passed = Vec::new();
+        for _ in 0..3 {
+            columns = moved_vertically(&doc_switcher, &mut selection, -1, false, columns);
+            passed.push(heads(&selection));
+        }
+        assert_eq!(
+            passed,
+            [
+                [pos(0, 2), pos(2, 2)],
+                [pos(1, 1), pos(2, 1)],
+                [pos(0, 0), pos(3, 0)],
+            ]
+        );
+        assert_eq!(columns, [1, 6]);
+    }
+
+    #[test]
+    fn test_moved_vertically_long_line() {
+        let text = format!("{}\nab\n\t{}", "a".repeat(20_000), "好".repeat(20_000));
+        let doc_switcher = switcher(&text);
+
+        // the column is counted from the line start, not from the part of the line on the screen.
+        let mut selection = Selection::point(pos(15_000, 0));
+        let columns = moved_vertically(&doc_switcher, &mut selection, 1, false, Vec::new());
+        assert_eq!(
+            (selection.cursor(), columns.as_slice()),
+            (pos(2, 1), [15_000].as_slice())
+        );
+        let columns = moved_vertically(&doc_switcher, &mut selection, 1, false, columns);
+        assert_eq!(selection.cursor(), pos(7_499, 2));
+        moved_vertically(&doc_switcher, &mut selection, -2, false, columns);
+        assert_eq!(selection.cursor(), pos(15_000, 0));
+    }
+
+    /// 100 lines, `line 0` to `line 99`.
+    fn lines() -> DocumentSwitcher {
+        let text: Vec<String> = (0..100).map(|y| format!("line {y}")).collect();
+        switcher(&text.join("\n"))
+    }
+
+    #[test]
+    fn test_paged() {
+        let doc_switcher = lines();
+        let (mut selection, mut offset) = (Selection::point(pos(2, 0)), pos(0, 0));
+
+        let rows = page_rows(KeyCode::PageDown, 1, 10);
+        paged(
+            &doc_switcher,
+            &mut selection,
+            &mut offset,
+            10,
+            rows,
+            false,
+            Vec::new(),
+        );
+        assert_eq!(
+            (selection.clone(), offset),
+            (Selection::point(pos(2, 10)), pos(0, 10))
+        );
+
+        // half a page, shift selects.
+        let rows = page_rows(KeyCode::PageDown, 2, 10);
+        let columns = paged(
+            &doc_switcher,
+            &mut selection,
+            &mut offset,
+            10,
+            rows,
+            true,
+            Vec::new(),
+        );
+        assert_eq!(selection.primary(), Range::new(pos(2, 10), pos(2, 15)));
+        assert_eq!(offset, pos(0, 15));
+        assert_eq!(columns, [2]);
+
+        let rows = page_rows(KeyCode::PageUp, 1, 10);
+        paged(
+            &doc_switcher,
+            &mut selection,
+            &mut offset,
+            10,
+            rows,
+            false,
+            columns,
+        );
+        paged(
+            &doc_switcher,
+            &mut selection,
+            &mut offset,
+            10,
+            rows,
+            false,
+            Vec::new(),
+        );
+        assert_eq!(
+            (selection.clone(), offset),
+            (Selection::point(pos(2, 0)), pos(0, 0))
+        );
+
+        // the last line stays at the bottom.
+        let mut selection = Selection::point(pos(2, 95));
+        let mut offset = pos(0, 85);
+        let rows = page_rows(KeyCode::PageDown, 1, 10);
+        paged(
+            &doc_switcher,
+            &mut selection,
+            &mut offset,
+            10,
+            rows,
+            false,
+            Vec::new(),
+        );
+        assert_eq!(
+            (selection, offset),
+            (Selection::point(pos(2, 99)), pos(0, 90))
+        );
+    }
+
+    #[test]
+    fn test_scrolled() {
+        let doc_switcher = lines();
+        let mut selection = Selection::point(pos(5, 0));
+        selection.push(Range::new(pos(0, 3), pos(2, 4)));
+        let mut offset = pos(0, 0);
+
+        scrolled(&doc_switcher, &mut selection, &mut offset, 5, -1);
+        assert_eq!(offset, pos(0, 0));
+        assert_eq!(
+            selection.ranges(),
+            [Range::point(pos(5, 0)), Range::new(pos(0, 3), pos(2, 4))]
+        );
+
+        // the cursor at the top stays a cursor, the selection below is still on the screen.
+        scrolled(&doc_switcher, &mut selection, &mut offset, 5, 2);
+        assert_eq!(offset, pos(0, 2));
+        assert_eq!(
+            selection.ranges(),
+            [Range::point(pos(5, 2)), Range::new(pos(0, 3), pos(2, 4))]
+        );
+
+        // the selection keeps its anchor and grows.
+        scrolled(&doc_switcher, &mut selection, &mut offset, 5, 3);
+        assert_eq!(offset, pos(0, 5));
+        assert_eq!(
+            selection.ranges(),
+            [Range::new(pos(0, 3), pos(2, 5)), Range::point(pos(5, 5))]
+        );
+
+        scrolled(&doc_switcher, &mut selection, &mut offset, 5, 1_000);
+        assert_eq!(offset, pos(0, 95));
+    }
+
+    #[test]
+    fn test_centered() {
+        assert_eq!(centered(50, 20), 40);
+        assert_eq!(centered(50, 21), 40);
+        assert_eq!(centered(3, 20), 0);
+    }
+
+    #[test]
+    fn test_document_edge() {
+        let doc_switcher = lines();
+        let end = |doc_switcher: &DocumentSwitcher, _| document_edge(doc_switcher, KeyCode::End);
+        let start = |doc_switcher: &DocumentSwitcher, _| document_edge(doc_switcher, KeyCode::Home);
+
+        let mut selection = Selection::point(pos(1, 1));
+        selection.push(Range::point(pos(2, 5)));
+        moved_heads(&doc_switcher, &mut selection, false, end);
+        assert_eq!(selection, Selection::point(pos(7, 99)));
+
+        let mut selection = Selection::point(pos(2, 5));
+        moved_heads(&doc_switcher, &mut selection, true, start);
+        assert_eq!(selection.primary(), Range::new(pos(2, 5), pos(0, 0)));
+        moved_heads(&doc_switcher, &mut selection, false, start);
+        assert_eq!(selection, Selection::point(pos(0, 0)));
+    }
+}
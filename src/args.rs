@@ -2,18 +2,40 @@ use std::env::args;
 
 #[derive(Default)]
 pub struct Args {
+    /// the files to open, `-` is stdin
     pub filenames: Vec<String>,
+    /// `--tab-width=N`
+    pub tab_width: Option<usize>,
 }
 
 impl Args {
     pub fn load() -> Self {
-        let mut default = Self::default();
-        let mut args = args().peekable().skip(1);
+        Self::parse(args().skip(1))
+    }
 
-        while let Some(arg) = args.next() {
-            default.filenames.push(arg)
+    pub fn parse(args: impl Iterator<Item = String>) -> Self {
+        let mut default = Self::default();
+        for arg in args {
+            match arg.strip_prefix("--tab-width=") {
+                Some(width) => default.tab_width = width.parse().ok().filter(|width| *width > 0),
+                None => default.filenames.push(arg),
+            }
         }
 
         default
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::args::Args;
+
+    #[test]
+    fn test_parse() {
+        let args = ["--tab-width=8", "-", "a.txt"].map(String::from);
+        let args = Args::parse(args.into_iter());
+        assert_eq!(args.tab_width, Some(8));
+        // `-` is kept as a file name, the switcher reads stdin for it.
+        assert_eq!(args.filenames, ["-", "a.txt"]);
+    }
+}
@@ -0,0 +1,178 @@
+//! the system clipboard through OSC 52 escape sequences.
+//!
+//! the sequences go through the terminal, so it works over ssh and inside tmux
+//! (with `set-clipboard on`) as long as the terminal supports them.
+
+use std::time::{Duration, Instant};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// ask the terminal for the content of the clipboard.
+pub const REQUEST: &str = "\x1b]52;c;?\x07";
+
+/// how long to wait for the answer to [`REQUEST`].
+const TIMEOUT: Duration = Duration::from_millis(500);
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// the sequence that puts `text` into the clipboard.
+pub fn copy_sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", encode(text.as_bytes()))
+}
+
+/// base64 with padding.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// decode base64, the padding is optional, returns `None` on any other character.
+pub fn decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text.trim_end_matches('=').bytes() {
+        let value = ALPHABET.iter().position(|a| *a == c)? as u32;
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+            n &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// what [`Read::feed`] made of a key.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Feed {
+    /// the key is part of the answer, wait for more.
+    Pending,
+    /// the answer is complete, `None` when the clipboard couldn't be read.
+    Done(Option<String>),
+    /// the key isn't part of an answer, the terminal doesn't support reading the clipboard.
+    Unexpected,
+}
+
+/// a pending read of the clipboard, the answer to [`REQUEST`] arrives as key events.
+///
+/// crossterm parses `ESC ]` as `Alt + ]`, the body as characters and the terminating
+/// `BEL` as `Ctrl + G` (or `ST` as `Alt + \`).
+#[derive(Debug)]
+pub struct Read {
+    started: bool,
+    body: String,
+    deadline: Instant,
+}
+
+impl Default for Read {
+    fn default() -> Self {
+        Self {
+            started: false,
+            body: String::new(),
+            deadline: Instant::now() + TIMEOUT,
+        }
+    }
+}
+
+impl Read {
+    /// the terminal didn't answer in time.
+    pub fn is_expired(&self) -> bool {
+        !self.started && Instant::now() >= self.deadline
+    }
+
+    pub fn feed(&mut self, key: KeyEvent) -> Feed {
+        let alt = key.modifiers == KeyModifiers::ALT;
+        match key.code {
+            KeyCode::Char(']') if alt && !self.started => {
+                self.started = true;
+                Feed::Pending
+            }
+            _ if !self.started => Feed::Unexpected,
+            KeyCode::Char('g') if key.modifiers == KeyModifiers::CONTROL => self.done(),
+            KeyCode::Char('\\') if alt => self.done(),
+            KeyCode::Char(c) => {
+                self.body.push(c);
+                Feed::Pending
+            }
+            _ => Feed::Done(None),
+        }
+    }
+
+    /// the body is `52;<selection>;<base64>`.
+    fn done(&self) -> Feed {
+        let text = self
+            .body
+            .strip_prefix("52;")
+            .and_then(|body| body.split_once(';'))
+            .and_then(|(_, text)| decode(text))
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+
+        Feed::Done(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    use crate::clipboard::{copy_sequence, decode, encode, Feed, Read};
+
+    #[test]
+    fn test_base64() {
+        for (text, encoded) in [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("你好\n", "5L2g5aW9Cg=="),
+        ] {
+            assert_eq!(encode(text.as_bytes()), encoded);
+            assert_eq!(decode(encoded).unwrap(), text.as_bytes());
+        }
+        assert_eq!(decode("Zm8"), Some(b"fo".to_vec()));
+        assert_eq!(decode("Zm8*"), None);
+        assert_eq!(copy_sequence("foo"), "\x1b]52;c;Zm9v\x07");
+    }
+
+    #[test]
+    fn test_read() {
+        let key = |code, modifiers| KeyEvent::new(code, modifiers);
+        let mut read = Read::default();
+        assert_eq!(
+            read.feed(key(KeyCode::Char(']'), KeyModifiers::ALT)),
+            Feed::Pending
+        );
+        for c in "52;c;Zm9v".chars() {
+            let modifiers = match c.is_uppercase() {
+                true => KeyModifiers::SHIFT,
+                false => KeyModifiers::NONE,
+            };
+            assert_eq!(read.feed(key(KeyCode::Char(c), modifiers)), Feed::Pending);
+        }
+        assert_eq!(
+            read.feed(key(KeyCode::Char('g'), KeyModifiers::CONTROL)),
+            Feed::Done(Some("foo".to_string()))
+        );
+
+        let mut read = Read::default();
+        assert_eq!(
+            read.feed(key(KeyCode::Char('a'), KeyModifiers::NONE)),
+            Feed::Unexpected
+        );
+    }
+}
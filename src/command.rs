@@ -0,0 +1,79 @@
+use encoding_rs::Encoding;
+
+use crate::line_ending::LineEnding;
+
+/// a command typed into the command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// convert every line break of the document
+    LineEnding(LineEnding),
+    /// read the file again in another encoding
+    Encoding(&'static Encoding),
+    /// read the file again, the unsaved changes are lost
+    Reload,
+    /// take the unsaved changes of a crashed session from the swap file
+    Recover,
+    /// select the next occurrence of the text, hex bytes in a binary file
+    Find(String),
+}
+
+impl Command {
+    /// parse `<name> <args>`, the error is shown to the user.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arg = words.next();
+        // the rest of the line, it may have spaces.
+        let rest = line.trim_start()[name.len()..].trim();
+
+        match name {
+            "line-ending" | "le" => arg
+                .and_then(LineEnding::parse)
+                .map(Command::LineEnding)
+                .ok_or_else(|| "usage: line-ending lf|crlf".to_string()),
+            "encoding" | "enc" => match arg {
+                Some(label) => Encoding::for_label(label.as_bytes())
+                    .map(Command::Encoding)
+                    .ok_or_else(|| format!("unknown encoding: {}", label)),
+                None => Err("usage: encoding <name>, e.g. gbk".to_string()),
+            },
+            "reload" | "r" => Ok(Command::Reload),
+            "recover" => Ok(Command::Recover),
+            "find" | "f" if !rest.is_empty() => Ok(Command::Find(rest.to_string())),
+            "find" | "f" => Err("usage: find <text>, or hex bytes in a binary file".to_string()),
+            _ => Err(format!("unknown command: {}", name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::Command;
+    use crate::line_ending::LineEnding;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            Command::parse(" line-ending crlf"),
+            Ok(Command::LineEnding(LineEnding::Crlf))
+        );
+        assert_eq!(
+            Command::parse("le lf"),
+            Ok(Command::LineEnding(LineEnding::Lf))
+        );
+        assert!(Command::parse("line-ending mixed").is_err());
+        assert!(Command::parse("nope").is_err());
+        assert_eq!(
+            Command::parse("enc shift_jis"),
+            Ok(Command::Encoding(encoding_rs::SHIFT_JIS))
+        );
+        assert!(Command::parse("encoding nope").is_err());
+        assert_eq!(Command::parse("reload"), Ok(Command::Reload));
+        assert_eq!(Command::parse("recover"), Ok(Command::Recover));
+        assert_eq!(
+            Command::parse("find de ad be ef "),
+            Ok(Command::Find("de ad be ef".to_string()))
+        );
+        assert!(Command::parse("f").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+//! a line diff of two texts in the unified format, e.g. the buffer against the file on disk.
+
+/// how big the table of the longest common subsequence may get,
+/// beyond it the changed middle of the texts is shown as removed and added as a whole.
+const MAX_TABLE: usize = 4_000_000;
+
+/// how many unchanged lines are shown around a change.
+const CONTEXT: usize = 3;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+/// the changes from `old` to `new` as a unified diff, empty when they have the same lines.
+pub fn unified(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let (old, new): (Vec<&str>, Vec<&str>) = (old.lines().collect(), new.lines().collect());
+    let ops = diff(&old, &new);
+
+    let changes: Vec<usize> = (0..ops.len()).filter(|i| ops[*i].0 != Op::Same).collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut text = format!("--- {}\n+++ {}\n", old_name, new_name);
+    let mut i = 0;
+    while i < changes.len() {
+        // changes closer than twice the context share a hunk.
+        let mut last = i;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT {
+            last += 1;
+        }
+        let start = changes[i].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(ops.len());
+        text.push_str(&hunk(&ops, start, end));
+        i = last + 1;
+    }
+    text
+}
+
+/// the ops from `start` to `end` with their header.
+fn hunk(ops: &[(Op, &str)], start: usize, end: usize) -> String {
+    let count = |ops: &[(Op, &str)], op| {
+        ops.iter()
+            .filter(|(o, _)| *o == Op::Same || *o == op)
+            .count()
+    };
+    let (old_before, new_before) = (
+        count(&ops[..start], Op::Removed),
+        count(&ops[..start], Op::Added),
+    );
+    let (old_len, new_len) = (
+        count(&ops[start..end], Op::Removed),
+        count(&ops[start..end], Op::Added),
+    );
+    // an empty side starts at the line before it.
+    let line = |before: usize, len: usize| before + (len > 0) as usize;
+
+    let mut text = format!(
+        "@@ -{},{} +{},{} @@\n",
+        line(old_before, old_len),
+        old_len,
+        line(new_before, new_len),
+        new_len
+    );
+    for (op, line) in &ops[start..end] {
+        let prefix = match op {
+            Op::Same => ' ',
+            Op::Removed => '-',
+            Op::Added => '+',
+        };
+        text.push(prefix);
+        text.push_str(line);
+        text.push('\n');
+    }
+    text
+}
+
+/// the lines of `old` and `new` in order, each marked as kept, removed or added.
+fn diff<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(Op, &'a str)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    let mut ops: Vec<(Op, &str)> = old[..prefix].iter().map(|line| (Op::Same, *line)).collect();
+    if (a.len() + 1) * (b.len() + 1) > MAX_TABLE {
+        ops.extend(a.iter().map(|line| (Op::Removed, *line)));
+        ops.extend(b.iter().map(|line| (Op::Added, *line)));
+    } else {
+        // lcs[i][j]: the longest common subsequence of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * width + j] = match a[i] == b[j] {
+                    true => lcs[(i + 1) * width + j + 1] + 1,
+                    false => lcs[(i + 1) * width + j].max(lcs[i * width + j + 1]),
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                ops.push((Op::Same, a[i]));
+                (i, j) = (i + 1, j + 1);
+            } else if i < a.len()
+                && (j == b.len() || lcs[(i + 1) * width + j] >= lcs[i * width + j + 1])
+            {
+                // removed lines come before the added ones.
+                ops.push((Op::Removed, a[i]));
+                i += 1;
+            } else {
+                ops.push((Op::Added, b[j]));
+                j += 1;
+            }
+        }
+    }
+    ops.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| (Op::Same, *line)),
+    );
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::unified;
+
+    #[test]
+    fn test_unified() {
+        assert_eq!(unified("a\nb\n", "a\nb\n", "old", "new"), "");
+
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n";
+        let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n10\n11\n12\n13\n";
+        assert_eq!(
+            unified(old, new, "old", "new"),
+            "--- old\n+++ new\n\
+             @@ -2,7 +2,7 @@\n 2\n 3\n 4\n-5\n+five\n 6\n 7\n 8\n\
+             @@ -10,3 +10,4 @@\n 10\n 11\n 12\n+13\n"
+        );
+        assert_eq!(
+            unified("1\n2\n", "1\n2\n3\n", "old", "new"),
+            "--- old\n+++ new\n@@ -1,2 +1,3 @@\n 1\n 2\n+3\n"
+        );
+        assert_eq!(
+            unified("1\n2\n", "", "old", "new"),
+            "--- old\n+++ new\n@@ -1,2 +0,0 @@\n-1\n-2\n"
+        );
+
+        assert_eq!(
+            unified("a\nb\nc\n", "b\nc\nd\n", "old", "new"),
+            "--- old\n+++ new\n@@ -1,3 +1,3 @@\n-a\n b\n c\n+d\n"
+        );
+    }
+}
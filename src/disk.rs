@@ -0,0 +1,110 @@
+//! what a file looked like on disk, to notice when something else changes it.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::state::hash;
+
+/// the modification time, size and hash of a file when it was last read or written.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DiskState {
+    modified: Option<SystemTime>,
+    size: u64,
+    /// `None` for large files, hashing them would read them again
+    hash: Option<u64>,
+}
+
+/// what [`DiskState::check`] found.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiskChange {
+    Unchanged,
+    /// the content on disk is different now
+    Changed(DiskState),
+    /// the file is gone
+    Deleted,
+}
+
+impl DiskState {
+    /// the state of `bytes` just read from `path`.
+    pub fn from_bytes(path: &Path, bytes: &[u8]) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            size: metadata.len(),
+            hash: Some(hash(bytes)),
+        })
+    }
+
+    /// read the state of `path`, the content is only hashed when `hashed`.
+    pub fn read(path: &Path, hashed: bool) -> io::Result<Self> {
+        match hashed {
+            true => Self::from_bytes(path, &fs::read(path)?),
+            false => {
+                let metadata = fs::metadata(path)?;
+                Ok(Self {
+                    modified: metadata.modified().ok(),
+                    size: metadata.len(),
+                    hash: None,
+                })
+            }
+        }
+    }
+
+    /// has the file at `path` changed since this state was taken?
+    ///
+    /// the modification time and size are checked first, a file that was only touched has the same hash
+    /// and its new state is returned as [`DiskChange::Unchanged`] through `self`.
+    pub fn check(&mut self, path: &Path) -> DiskChange {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return DiskChange::Deleted,
+            Err(_) => return DiskChange::Unchanged,
+        };
+        if metadata.modified().ok() == self.modified && metadata.len() == self.size {
+            return DiskChange::Unchanged;
+        }
+
+        let state = match Self::read(path, self.hash.is_some()) {
+            Ok(state) => state,
+            Err(_) => return DiskChange::Unchanged,
+        };
+        if state.hash.is_some() && state.hash == self.hash {
+            *self = state;
+            return DiskChange::Unchanged;
+        }
+        DiskChange::Changed(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use crate::disk::{DiskChange, DiskState};
+
+    #[test]
+    fn test_check() {
+        let path = std::env::temp_dir().join(format!("raw-disk-{}.txt", std::process::id()));
+        std::fs::write(&path, "a").unwrap();
+        let mut state = DiskState::read(&path, true).unwrap();
+        assert_eq!(state.check(&path), DiskChange::Unchanged);
+
+        // touched, the same content.
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(state.check(&path), DiskChange::Unchanged);
+        assert_eq!(state, DiskState::read(&path, true).unwrap());
+
+        std::fs::write(&path, "b").unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(20))
+            .unwrap();
+        let changed = DiskState::read(&path, true).unwrap();
+        assert_eq!(state.check(&path), DiskChange::Changed(changed));
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(state.check(&path), DiskChange::Deleted);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::bail;
+use encoding_rs::{Encoding, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+use crate::app::AppResult;
+
+/// detect the encoding of `bytes`, returns the encoding and the length of its BOM.
+///
+/// without a BOM it's UTF-8 when the bytes are valid UTF-8, then the better guess of GBK and
+/// Shift_JIS, and Latin-1 (as windows-1252) when neither can decode the bytes.
+pub fn detect(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom)) = Encoding::for_bom(bytes) {
+        return (encoding, bom);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+
+    // on a tie GBK wins, it's the one used more often here.
+    let guess = [SHIFT_JIS, GBK]
+        .into_iter()
+        .filter_map(|encoding| {
+            let text = encoding.decode_without_bom_handling_and_without_replacement(bytes)?;
+            Some((encoding, score(&text)))
+        })
+        .max_by_key(|(_, score)| *score);
+
+    match guess {
+        Some((encoding, _)) => (encoding, 0),
+        None => (WINDOWS_1252, 0),
+    }
+}
+
+/// how much `text` looks like real CJK text.
+///
+/// kana only shows up in Japanese, halfwidth katakana is what GBK looks like decoded as Shift_JIS.
+fn score(text: &str) -> isize {
+    text.chars()
+        .map(|c| match c {
+            '\u{3040}'..='\u{30ff}' => 2,
+            '\u{4e00}'..='\u{9fff}' | '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff60}' => 1,
+            '\u{ff61}'..='\u{ff9f}' => -1,
+            c if c.is_ascii() => 0,
+            _ => -1,
+        })
+        .sum()
+}
+
+/// how many bytes [`is_binary`] looks at.
+const BINARY_BLOCK: usize = 8192;
+
+/// does the first block of `bytes` look like a binary file rather than text?
+///
+/// it's binary with NUL bytes outside of UTF-16, or when it isn't UTF-8 and has control
+/// characters no text in a legacy encoding would have.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    if Encoding::for_bom(bytes).is_some() {
+        return false;
+    }
+
+    let block = &bytes[..bytes.len().min(BINARY_BLOCK)];
+    if block.contains(&0) {
+        return true;
+    }
+
+    // a char cut off at the end of the block is still UTF-8.
+    let utf8 = match std::str::from_utf8(block) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    !utf8
+        && block
+            .iter()
+            .any(|byte| matches!(byte, 0x01..=0x08 | 0x0e..=0x1a | 0x1c..=0x1f | 0x7f))
+}
+
+/// decode `bytes` (without the BOM), fails when they aren't valid in the encoding.
+pub fn decode(bytes: &[u8], encoding: &'static Encoding) -> AppResult<String> {
+    match encoding.decode_without_bom_handling_and_without_replacement(bytes) {
+        Some(text) => Ok(text.into_owned()),
+        None => bail!("not valid {}", encoding.name()),
+    }
+}
+
+/// encode `text` back to bytes, fails when a char doesn't exist in the encoding.
+pub fn encode(text: &str, encoding: &'static Encoding, bom: bool) -> AppResult<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    if encoding == UTF_16LE || encoding == UTF_16BE {
+        // encoding_rs only encodes to UTF-16 through UTF-8.
+        let le = encoding == UTF_16LE;
+        if bom {
+            bytes.extend_from_slice(if le { &[0xff, 0xfe] } else { &[0xfe, 0xff] });
+        }
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&if le {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+        return Ok(bytes);
+    }
+
+    if bom && encoding == UTF_8 {
+        bytes.extend_from_slice(&[0xef, 0xbb, 0xbf]);
+    }
+    let (encoded, _, unmappable) = encoding.encode(text);
+    if unmappable {
+        bail!("the text can't be encoded in {}", encoding.name());
+    }
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{GBK, SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use crate::encoding::{decode, detect, encode, is_binary};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("abc 你好".as_bytes()), (UTF_8, 0));
+        assert_eq!(detect(&[0xef, 0xbb, 0xbf, b'a']), (UTF_8, 3));
+        assert_eq!(detect(&[0xff, 0xfe, b'a', 0]), (UTF_16LE, 2));
+
+        let text = "编码检测，简体中文的文本。";
+        let gbk = encode(text, GBK, false).unwrap();
+        assert_eq!(detect(&gbk), (GBK, 0));
+        assert_eq!(decode(&gbk, GBK).unwrap(), text);
+
+        let text = "こんにちは、世界。ひらがなとカタカナ。";
+        let sjis = encode(text, SHIFT_JIS, false).unwrap();
+        assert_eq!(detect(&sjis), (SHIFT_JIS, 0));
+
+        assert_eq!(detect(b"caf\xe9"), (WINDOWS_1252, 0));
+        assert_eq!(decode(b"caf\xe9", WINDOWS_1252).unwrap(), "café");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(
+            encode("a好", UTF_16LE, true).unwrap(),
+            [0xff, 0xfe, b'a', 0, 0x7d, 0x59]
+        );
+        assert_eq!(encode("a", UTF_8, true).unwrap(), [0xef, 0xbb, 0xbf, b'a']);
+        assert!(encode("好", WINDOWS_1252, false).is_err());
+        assert!(decode(b"\xff\xff", UTF_8).is_err());
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"text\r\n\t\x1b[0m"));
+        assert!(!is_binary(b"caf\xe9"));
+        assert!(!is_binary(&[0xff, 0xfe, b'a', 0]));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n"));
+
+        let mut text = "好".repeat(super::BINARY_BLOCK).into_bytes();
+        text.push(0xff);
+        assert!(!is_binary(&text));
+    }
+}
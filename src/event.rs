@@ -5,7 +5,7 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 /// Terminal events.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub enum Event {
     /// Terminal tick.
     Tick,
@@ -15,16 +15,16 @@ pub enum Event {
     Mouse(MouseEvent),
     /// Terminal resize.
     Resize(u16, u16),
+    /// Bracketed paste, the whole pasted text.
+    Paste(String),
+    /// The terminal window got the focus back.
+    FocusGained,
 }
 
-/// Terminal event handler.
+/// Terminal event handler, a thread sends the events until the app exits.
 pub struct EventHandler {
-    /// Event sender channel.
-    sender: mpsc::Sender<Event>,
     /// Event receiver channel.
     receiver: mpsc::Receiver<Event>,
-    /// Event handler thread.
-    handler: thread::JoinHandle<()>,
 }
 
 impl EventHandler {
@@ -32,36 +32,34 @@ impl EventHandler {
     pub fn new(tick_rate: u64) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
-        let handler = {
-            let sender = sender.clone();
-            thread::spawn(move || {
-                let mut last_tick = Instant::now();
-                loop {
-                    let timeout = tick_rate
-                        .checked_sub(last_tick.elapsed())
-                        .unwrap_or(tick_rate);
+        thread::spawn(move || {
+            let mut last_tick = Instant::now();
+            loop {
+                let timeout = tick_rate
+                    .checked_sub(last_tick.elapsed())
+                    .unwrap_or(tick_rate);
 
-                    if poll(timeout).expect("no events available") {
-                        match event::read().expect("unable to read event") {
-                            CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
-                            CrosstermEvent::Mouse(e) => sender.send(Event::Mouse(e)),
-                            CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
-                        }
-                        .expect("failed to send terminal event")
+                // crossterm reads the keys from `/dev/tty` when stdin isn't a terminal,
+                // that's what keeps `cmd | raw -` working, see `DocumentSwitcher::load`.
+                if poll(timeout).expect("no events available") {
+                    match event::read().expect("unable to read event") {
+                        CrosstermEvent::Key(e) => sender.send(Event::Key(e)),
+                        CrosstermEvent::Mouse(e) => sender.send(Event::Mouse(e)),
+                        CrosstermEvent::Resize(w, h) => sender.send(Event::Resize(w, h)),
+                        CrosstermEvent::Paste(text) => sender.send(Event::Paste(text)),
+                        CrosstermEvent::FocusGained => sender.send(Event::FocusGained),
+                        CrosstermEvent::FocusLost => Ok(()),
                     }
+                    .expect("failed to send terminal event")
+                }
 
-                    if last_tick.elapsed() >= tick_rate {
-                        sender.send(Event::Tick).expect("failed to send tick event");
-                        last_tick = Instant::now();
-                    }
+                if last_tick.elapsed() >= tick_rate {
+                    sender.send(Event::Tick).expect("failed to send tick event");
+                    last_tick = Instant::now();
                 }
-            })
-        };
-        Self {
-            sender,
-            receiver,
-            handler,
-        }
+            }
+        });
+        Self { receiver }
     }
 
     /// Receive the next event from the handler thread.
@@ -79,6 +77,9 @@ pub fn read() -> AppResult<Event> {
         CrosstermEvent::Key(e) => Event::Key(e),
         CrosstermEvent::Mouse(e) => Event::Mouse(e),
         CrosstermEvent::Resize(w, h) => Event::Resize(w, h),
+        CrosstermEvent::Paste(text) => Event::Paste(text),
+        CrosstermEvent::FocusGained => Event::FocusGained,
+        CrosstermEvent::FocusLost => Event::Tick,
     };
     Ok(event)
 }
@@ -1,19 +1,17 @@
-use ropey::RopeSlice;
-use tui::layout::Rect;
-
 /// copy from (ropey author)[https://github.com/cessen/led/blob/master/src/graphemes.rs]
 pub mod graphemes_ex;
 /// the extension for [`tui::layout::Rect`]
 pub mod rect;
 /// the extension for [`ropey::RopeSlice`]
 pub mod rope;
+/// word boundaries for the word motions
+pub mod word;
 
-#[cfg(tests)]
+#[cfg(test)]
 mod tests {
     use crate::extension::graphemes_ex::next_grapheme_boundary;
     use crate::extension::rope::RopeSliceEx;
     use crate::render::document::Document;
-    use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
     use unicode_width::UnicodeWidthStr;
 
     #[test]
@@ -60,8 +58,8 @@ mod tests {
         println!("{}", slice.slice(3..4)); // 🔬
         println!("{}", slice.slice(4..7));
 
-        println!("{}", line.offset_mapping.get(0).unwrap());
-        println!("{}", line.str_list.get(0).unwrap());
+        println!("{}", line.offset_mapping.first().unwrap());
+        println!("{}", line.str_list.first().unwrap());
         println!("{}", line.offset_mapping.get(1).unwrap());
         println!("{}", line.str_list.get(1).unwrap());
         println!("{}", line.offset_mapping.get(2).unwrap());
@@ -70,7 +68,7 @@ mod tests {
         println!("{}", line.str_list.get(3).unwrap());
         println!("{}", line.offset_mapping.get(4).unwrap());
         println!("{}", line.str_list.get(4).unwrap());
-        println!("{}", line.to_string());
+        println!("{}", line);
         // println!("{}", line.width);
         // println!(
         //     "{}",
@@ -82,4 +80,35 @@ mod tests {
         // );
         // println!("{}", line.get_next_width(2))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tab() {
+        let line = ropey::RopeSlice::from("a\tb\t\tc").to_line_with_tab(4);
+        assert_eq!(line.offset_mapping, [0, 1, 4, 5, 8, 12]);
+        assert_eq!(line.grapheme_width(1), 3);
+        assert_eq!(line.get_offset(6), 13);
+        assert_eq!(line.render(0), "a   b       c");
+        assert_eq!(line.render(2), "b       c");
+        assert_eq!(line.grapheme_at(6), Some(3));
+    }
+
+    #[test]
+    fn test_window() {
+        let slice = ropey::RopeSlice::from("a你\tbcdef\n");
+        let line = slice.slice(1..).to_window(4, 1, 1, 5);
+        assert_eq!(line.start, 1);
+        // `\t`在整行的第3列, 只到第4列
+        assert_eq!(line.offset_mapping, [0, 2, 3, 4]);
+        assert_eq!(line.get_offset(0), 0);
+        assert_eq!(line.get_offset(2), 2);
+        assert_eq!(line.get_offset(5), 5);
+        assert_eq!(line.origin(1), 0);
+        assert_eq!(line.len(), 5);
+        assert_eq!(line.grapheme_at(2), Some(2));
+        assert_eq!(line.render(2), " bc");
+
+        let line = slice.slice(9..).to_window(4, 9, 12, 5);
+        assert_eq!((line.start, line.len(), line.width), (9, 9, 0));
+        assert_eq!(slice.len_word_boundary(), 9);
+    }
+}
@@ -340,4 +340,4 @@ impl<'a> Iterator for RopeGraphemes<'a> {
             Some((&self.cur_chunk[a2..b2]).into())
         }
     }
-}
\ No newline at end of file
+}
@@ -1,17 +1,31 @@
-use ropey::iter::Chunks;
-use ropey::str_utils::byte_to_char_idx;
+use std::borrow::Cow;
+use std::fmt;
+
 use ropey::RopeSlice;
-use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete, UnicodeSegmentation};
+use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use crate::extension::graphemes_ex::RopeGraphemes;
+use crate::DEFAULT_TAB_WIDTH;
+
 pub trait RopeSliceEx<'a> {
     /// repo slice to string
     fn get_string(&self) -> String;
 
     fn to_line(&self) -> Line;
 
+    /// like [`RopeSliceEx::to_line`], `\t` expands to the next multiple of `tab_width`.
+    fn to_line_with_tab(&self, tab_width: usize) -> Line;
+
+    /// 只转换开头占满`width`列的部分, 很长的一行不会整行复制.
+    /// 切片从整行的第`start`个字素簇、第`origin`列开始, 列从切片开头计算, `\t`按整行的列对齐
+    fn to_window(&self, tab_width: usize, start: usize, origin: usize, width: usize) -> Line;
+
     /// O(n) get len
     fn len_word_boundary(&self) -> usize;
+
+    /// `text`第一次出现的字符下标, 按块查找, 不会把整个切片复制成字符串
+    fn find_str(&self, text: &str) -> Option<usize>;
 }
 
 #[derive(Default, Clone)]
@@ -19,21 +33,58 @@ pub struct Line {
     pub offset_mapping: Vec<usize>,
     pub str_list: Vec<String>,
     pub width: usize,
+    /// 第一个字素簇在整行中的下标, 只转换了一部分时不为0, 见[`RopeSliceEx::to_window`]
+    pub start: usize,
 }
 
 impl Line {
     /// 获取x在当前行中的开头的offset
     pub fn get_offset(&self, x: usize) -> usize {
-        if let Some(offset) = self.offset_mapping.get(x) {
-            *offset
-        } else {
-            // 当做最后一个字符处理
-            if let Some(str) = self.str_list.get(x.saturating_sub(1)) {
-                str.width() + self.get_offset(x.saturating_sub(1))
-            } else {
-                0
-            }
+        if x < self.start {
+            return 0;
         }
+
+        match self.offset_mapping.get(x - self.start) {
+            Some(offset) => *offset,
+            // 超出行尾, 即整行的宽度
+            None => self.width,
+        }
+    }
+
+    /// 向右滚动了`offset_x`个字素簇时, 视图从哪一列开始; 不滚动时总是第0列
+    pub fn origin(&self, offset_x: usize) -> usize {
+        match offset_x <= self.start {
+            true => 0,
+            false => self.get_offset(offset_x),
+        }
+    }
+
+    /// 第x个字素簇的显示宽度, `\t`的宽度取决于它所在的列
+    pub fn grapheme_width(&self, x: usize) -> usize {
+        self.get_offset(x + 1) - self.get_offset(x)
+    }
+
+    /// 不包含换行符的字素簇数量, 只转换了一部分时是这部分末尾的下标
+    pub fn len(&self) -> usize {
+        self.start
+            + self
+                .str_list
+                .iter()
+                .take_while(|str| !str.ends_with(['\n', '\r']))
+                .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 显示列`column`所在的字素簇, 宽字符的两列都落在同一个字素簇上,
+    /// 超出行尾时返回`None`
+    pub fn grapheme_at(&self, column: usize) -> Option<usize> {
+        (self.start..self.len()).find(|x| {
+            let offset = self.get_offset(*x);
+            offset <= column && column < offset + self.grapheme_width(*x).max(1)
+        })
     }
 
     pub fn render(&self, offset: usize) -> String {
@@ -42,8 +93,12 @@ impl Line {
         }
 
         let mut result = String::new();
-        for string in self.str_list.iter().skip(offset) {
-            result.push_str(string);
+        let skip = offset.saturating_sub(self.start);
+        for (x, string) in self.str_list.iter().enumerate().skip(skip) {
+            match string.as_str() {
+                "\t" => result.push_str(&" ".repeat(self.grapheme_width(self.start + x))),
+                _ => result.push_str(string),
+            }
         }
         result
     }
@@ -55,12 +110,20 @@ impl<'a> RopeSliceEx<'a> for RopeSlice<'a> {
     }
 
     fn to_line(&self) -> Line {
+        self.to_line_with_tab(DEFAULT_TAB_WIDTH)
+    }
+
+    fn to_line_with_tab(&self, tab_width: usize) -> Line {
+        let tab_width = tab_width.max(1);
         let mut width = 0;
         let mut str_list = Vec::new();
         let mut offset_mapping = Vec::new();
         for str in self.get_string().graphemes(true) {
             str_list.push(str.to_string());
-            let raw_len = str.width();
+            let raw_len = match str {
+                "\t" => tab_width - width % tab_width,
+                _ => str.width(),
+            };
             offset_mapping.push(width);
             width += raw_len;
         }
@@ -69,6 +132,34 @@ impl<'a> RopeSliceEx<'a> for RopeSlice<'a> {
             offset_mapping,
             str_list,
             width,
+            start: 0,
+        }
+    }
+
+    fn to_window(&self, tab_width: usize, start: usize, origin: usize, width: usize) -> Line {
+        let tab_width = tab_width.max(1);
+        let mut column = 0;
+        let mut str_list = Vec::new();
+        let mut offset_mapping = Vec::new();
+        for grapheme in RopeGraphemes::new(*self) {
+            if column >= width {
+                break;
+            }
+
+            let str: Cow<str> = grapheme.into();
+            offset_mapping.push(column);
+            column += match str.as_ref() {
+                "\t" => tab_width - (origin + column) % tab_width,
+                str => str.width(),
+            };
+            str_list.push(str.into_owned());
+        }
+
+        Line {
+            offset_mapping,
+            str_list,
+            width: column,
+            start,
         }
     }
 
@@ -76,25 +167,44 @@ impl<'a> RopeSliceEx<'a> for RopeSlice<'a> {
     /// 1  => 1
     /// 你 => 1
     fn len_word_boundary(&self) -> usize {
-        let mut width = 0;
-        for _ in self.get_string().graphemes(true) {
-            width += 1;
+        RopeGraphemes::new(*self).count()
+    }
+
+    fn find_str(&self, text: &str) -> Option<usize> {
+        let needle = text.as_bytes();
+        if needle.is_empty() {
+            return Some(0);
         }
 
-        width
-    }
-}
+        // 前面块的最后`needle.len() - 1`个字节, 跨块的匹配从这里开始
+        let mut carry: Vec<u8> = Vec::new();
+        let mut byte = 0;
+        for chunk in self.chunks() {
+            let head = &chunk.as_bytes()[..chunk.len().min(needle.len() - 1)];
+            let window = [carry.as_slice(), head].concat();
+            let found = window
+                .windows(needle.len())
+                .position(|bytes| bytes == needle)
+                .map(|start| byte - carry.len() + start)
+                .or_else(|| chunk.find(text).map(|start| byte + start));
+            if let Some(start) = found {
+                return Some(self.byte_to_char(start));
+            }
 
-impl ToString for Line {
-    fn to_string(&self) -> String {
-        if self.str_list.is_empty() {
-            return "".to_string();
+            let tail = chunk.len().saturating_sub(needle.len() - 1);
+            carry.extend_from_slice(&chunk.as_bytes()[tail..]);
+            carry.drain(..carry.len().saturating_sub(needle.len() - 1));
+            byte += chunk.len();
         }
+        None
+    }
+}
 
-        let mut result = String::new();
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for string in self.str_list.iter() {
-            result.push_str(string);
+            f.write_str(string)?;
         }
-        result
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,204 @@
+//! word boundaries within a line, for the word motions and deletions.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// what the word motions stop at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WordKind {
+    /// the words of the unicode word segmentation, a run of punctuation or CJK counts as one
+    Word,
+    /// everything between whitespace
+    BigWord,
+    /// the parts of a camelCase or snake_case word
+    Subword,
+}
+
+/// how a segment of the unicode word segmentation is joined with its neighbours.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Class {
+    Space,
+    Word,
+    Punctuation,
+    Cjk,
+    /// e.g. an emoji
+    Other,
+}
+
+/// the end of the first word in `text` that ends after the byte `at`.
+pub fn next_end(text: &str, at: usize, kind: WordKind) -> Option<usize> {
+    words(text, kind)
+        .into_iter()
+        .map(|(_, end)| end)
+        .find(|end| *end > at)
+}
+
+/// the start of the last word in `text` that starts before the byte `at`.
+pub fn prev_start(text: &str, at: usize, kind: WordKind) -> Option<usize> {
+    words(text, kind)
+        .into_iter()
+        .rev()
+        .map(|(start, _)| start)
+        .find(|start| *start < at)
+}
+
+/// the words of `text` as byte ranges, whitespace is never part of them.
+fn words(text: &str, kind: WordKind) -> Vec<(usize, usize)> {
+    if kind == WordKind::BigWord {
+        let mut words = Vec::new();
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            match (c.is_whitespace(), start) {
+                (true, Some(from)) => {
+                    words.push((from, i));
+                    start = None;
+                }
+                (false, None) => start = Some(i),
+                _ => {}
+            }
+        }
+        words.extend(start.map(|from| (from, text.len())));
+        return words;
+    }
+
+    let mut words: Vec<(usize, usize, Class)> = Vec::new();
+    for (start, segment) in text.split_word_bound_indices() {
+        let class = class(segment);
+        let end = start + segment.len();
+        match words.last_mut() {
+            Some(last)
+                if last.1 == start
+                    && last.2 == class
+                    && matches!(class, Class::Punctuation | Class::Cjk) =>
+            {
+                last.1 = end
+            }
+            _ if class == Class::Space => {}
+            _ => words.push((start, end, class)),
+        }
+    }
+
+    let mut result = Vec::new();
+    for (start, end, class) in words {
+        match kind == WordKind::Subword && class == Class::Word {
+            true => subwords(&text[start..end], start, &mut result),
+            false => result.push((start, end)),
+        }
+    }
+    result
+}
+
+fn class(segment: &str) -> Class {
+    let c = segment.chars().next().unwrap_or(' ');
+    if c.is_whitespace() {
+        Class::Space
+    } else if is_cjk(c) {
+        Class::Cjk
+    } else if c.is_alphanumeric() || c == '_' {
+        Class::Word
+    } else if c < '\u{2c00}' || ('\u{3000}'..='\u{303f}').contains(&c) {
+        // latin and general punctuation, symbols and the CJK punctuation.
+        Class::Punctuation
+    } else {
+        Class::Other
+    }
+}
+
+/// han, kana and hangul, the segmentation splits them into single chars.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{1100}'..='\u{11ff}'
+        | '\u{3040}'..='\u{30ff}'
+        | '\u{3400}'..='\u{4dbf}'
+        | '\u{4e00}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{20000}'..='\u{2fa1f}')
+}
+
+/// split `word` at `_` and where the case changes: `parseHTTPResponse` is `parse`, `HTTP` and `Response`.
+fn subwords(word: &str, offset: usize, result: &mut Vec<(usize, usize)>) {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut start = None;
+    for (i, &(at, c)) in chars.iter().enumerate() {
+        if c == '_' {
+            result.extend(start.take().map(|from| (offset + from, offset + at)));
+            continue;
+        }
+
+        let prev = i.checked_sub(1).map(|i| chars[i].1);
+        let next = chars.get(i + 1).map(|(_, c)| *c);
+        let hump = match (prev, next) {
+            (Some(prev), _) if prev.is_lowercase() && c.is_uppercase() => true,
+            (Some(prev), Some(next)) => {
+                prev.is_uppercase() && c.is_uppercase() && next.is_lowercase()
+            }
+            _ => false,
+        };
+        match start {
+            Some(from) if hump => {
+                result.push((offset + from, offset + at));
+                start = Some(at);
+            }
+            None => start = Some(at),
+            _ => {}
+        }
+    }
+    result.extend(start.map(|from| (offset + from, offset + word.len())));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extension::word::{next_end, prev_start, words, WordKind};
+
+    fn strs(text: &str, kind: WordKind) -> Vec<&str> {
+        words(text, kind)
+            .into_iter()
+            .map(|(start, end)| &text[start..end])
+            .collect()
+    }
+
+    #[test]
+    fn test_words() {
+        let text = "let parseHTTPResponse = snake_case(a.b);";
+        assert_eq!(
+            strs(text, WordKind::Word),
+            // `a.b` is one word, like `e.g`.
+            [
+                "let",
+                "parseHTTPResponse",
+                "=",
+                "snake_case",
+                "(",
+                "a.b",
+                ");"
+            ]
+        );
+        assert_eq!(
+            strs(text, WordKind::BigWord),
+            ["let", "parseHTTPResponse", "=", "snake_case(a.b);"]
+        );
+        assert_eq!(
+            strs(text, WordKind::Subword),
+            ["let", "parse", "HTTP", "Response", "=", "snake", "case", "(", "a.b", ");"]
+        );
+
+        assert_eq!(
+            strs("你好世界, raw编辑器 👨‍👩‍👧👍", WordKind::Word),
+            ["你好世界", ",", "raw", "编辑器", "👨‍👩‍👧", "👍"]
+        );
+    }
+
+    #[test]
+    fn test_next_and_prev() {
+        let text = "foo  bar_baz";
+        assert_eq!(next_end(text, 0, WordKind::Word), Some(3));
+        assert_eq!(next_end(text, 3, WordKind::Word), Some(12));
+        assert_eq!(next_end(text, 3, WordKind::Subword), Some(8));
+        assert_eq!(next_end(text, 12, WordKind::Word), None);
+
+        assert_eq!(prev_start(text, 12, WordKind::Word), Some(5));
+        assert_eq!(prev_start(text, 12, WordKind::Subword), Some(9));
+        assert_eq!(prev_start(text, 5, WordKind::Word), Some(0));
+        assert_eq!(prev_start(text, 0, WordKind::Word), None);
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use crate::indent::Indent;
+
+/// what the editor knows about a kind of file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileType {
+    pub name: &'static str,
+    /// file extensions, or whole file names like `Makefile`
+    patterns: &'static [&'static str],
+    /// the pairs of brackets, the next line after an opening bracket is indented
+    brackets: &'static [(char, char)],
+    /// a line ending with `:` indents the next line
+    colon: bool,
+}
+
+const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+const FILETYPES: &[FileType] = &[
+    FileType {
+        name: "rust",
+        patterns: &["rs"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "c",
+        patterns: &["c", "h", "cc", "cpp", "hpp"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "go",
+        patterns: &["go"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "java",
+        patterns: &["java", "kt"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "javascript",
+        patterns: &["js", "ts", "jsx", "tsx"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "json",
+        patterns: &["json"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "toml",
+        patterns: &["toml"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "shell",
+        patterns: &["sh", "bash", "zsh"],
+        brackets: BRACKETS,
+        colon: false,
+    },
+    FileType {
+        name: "python",
+        patterns: &["py"],
+        brackets: BRACKETS,
+        colon: true,
+    },
+    FileType {
+        name: "yaml",
+        patterns: &["yml", "yaml"],
+        brackets: BRACKETS,
+        colon: true,
+    },
+    FileType {
+        name: "makefile",
+        patterns: &["Makefile", "makefile", "GNUmakefile", "mk"],
+        brackets: &[],
+        colon: true,
+    },
+    FileType {
+        name: "markdown",
+        patterns: &["md"],
+        brackets: &[],
+        colon: false,
+    },
+    FileType {
+        name: "text",
+        patterns: &["txt"],
+        brackets: &[],
+        colon: false,
+    },
+];
+
+/// detect the filetype by the file name, then by the extension.
+pub fn detect(path: &str) -> Option<&'static FileType> {
+    let path = Path::new(path);
+    let matches = |name: Option<&std::ffi::OsStr>| {
+        let name = name?.to_str()?;
+        FILETYPES
+            .iter()
+            .find(|filetype| filetype.patterns.contains(&name))
+    };
+
+    matches(path.file_name()).or_else(|| matches(path.extension()))
+}
+
+impl FileType {
+    /// does a line ending with `c` indent the next line?
+    pub fn indents_after(&self, c: char) -> bool {
+        (self.colon && c == ':') || self.brackets.iter().any(|(open, _)| *open == c)
+    }
+
+    /// the closing bracket of `open`.
+    pub fn closing(&self, open: char) -> Option<char> {
+        self.brackets
+            .iter()
+            .find(|(o, _)| *o == open)
+            .map(|(_, close)| *close)
+    }
+
+    pub fn is_closing(&self, c: char) -> bool {
+        self.brackets.iter().any(|(_, close)| *close == c)
+    }
+
+    /// the indentation used when the file itself doesn't tell.
+    pub fn default_indent(&self) -> Indent {
+        match self.name {
+            "makefile" | "go" => Indent::Tabs,
+            _ => Indent::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::filetype::detect;
+    use crate::render::document::Document;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("src/main.rs").map(|f| f.name), Some("rust"));
+        assert_eq!(detect("Makefile").map(|f| f.name), Some("makefile"));
+        assert_eq!(detect("a/b.yaml").map(|f| f.name), Some("yaml"));
+        assert_eq!(detect("README"), None);
+        let doc = Document::from(Rope::from("a"), "README");
+        assert_eq!(doc.filetype(), "text");
+
+        let python = detect("a.py").unwrap();
+        assert!(python.indents_after(':'));
+        assert!(python.indents_after('('));
+        assert!(!detect("a.rs").unwrap().indents_after(':'));
+        assert_eq!(python.closing('['), Some(']'));
+    }
+}
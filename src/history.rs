@@ -0,0 +1,571 @@
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail};
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::app::AppResult;
+use crate::screen::Position;
+use crate::selection::{Range, Selection};
+use crate::state::{create_file, hash_rope};
+
+/// typing that pauses longer than this starts a new undo step.
+const GROUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// the first line of an undo file.
+const UNDO_FILE_HEADER: &str = "raw undo v2";
+
+/// a reversible change of the content: `removed` at char index `at` is replaced by `inserted`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub at: usize,
+    pub removed: String,
+    pub inserted: String,
+}
+
+impl Change {
+    pub fn insert(at: usize, text: &str) -> Self {
+        Self {
+            at,
+            removed: String::new(),
+            inserted: text.to_string(),
+        }
+    }
+
+    pub fn remove(at: usize, text: &str) -> Self {
+        Self {
+            at,
+            removed: text.to_string(),
+            inserted: String::new(),
+        }
+    }
+
+    /// apply the change to the content.
+    pub fn apply(&self, content: &mut Rope) {
+        let removed_len = self.removed.chars().count();
+        if removed_len > 0 {
+            content.remove(self.at..self.at + removed_len);
+        }
+        if !self.inserted.is_empty() {
+            content.insert(self.at, &self.inserted);
+        }
+    }
+
+    /// the change that reverts this one.
+    pub fn invert(&self) -> Self {
+        Self {
+            at: self.at,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    /// merge a change made right after this one, used to group typing into one undo step.
+    fn merge(&self, next: &Change) -> Option<Change> {
+        if !is_typing(next) {
+            return None;
+        }
+
+        // insert: typing forward.
+        if self.removed.is_empty() && next.removed.is_empty() {
+            let end = self.at + self.inserted.chars().count();
+            let word_end = !ends_with_whitespace(&self.inserted)
+                && next.inserted.starts_with(char::is_whitespace);
+            if next.at != end || word_end {
+                return None;
+            }
+
+            return Some(Change::insert(
+                self.at,
+                &(self.inserted.clone() + &next.inserted),
+            ));
+        }
+
+        // remove: backspace or delete.
+        if self.inserted.is_empty() && next.inserted.is_empty() {
+            if next.at + next.removed.chars().count() == self.at {
+                return Some(Change::remove(
+                    next.at,
+                    &(next.removed.clone() + &self.removed),
+                ));
+            }
+            if next.at == self.at {
+                return Some(Change::remove(
+                    self.at,
+                    &(self.removed.clone() + &next.removed),
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+/// a single typed or deleted grapheme, not a line break.
+fn is_typing(change: &Change) -> bool {
+    let text = if change.removed.is_empty() {
+        &change.inserted
+    } else if change.inserted.is_empty() {
+        &change.removed
+    } else {
+        return false;
+    };
+
+    text.graphemes(true).count() == 1 && !text.contains('\n')
+}
+
+fn ends_with_whitespace(text: &str) -> bool {
+    text.chars().last().is_some_and(char::is_whitespace)
+}
+
+/// the view restored by undo / redo.
+#[derive(Clone, Default, Debug, PartialEq, Eq)]
+pub struct ViewState {
+    pub selection: Selection,
+    pub offset: Position,
+}
+
+/// changes that are undone / redone as one step.
+#[derive(Clone, Debug)]
+pub struct Transaction {
+    /// applied in order, each `at` refers to the content after the previous change
+    pub changes: Vec<Change>,
+    /// the view before the first change
+    pub before: ViewState,
+    /// the view after the last change
+    pub after: ViewState,
+    /// the document revision after this transaction
+    pub revision: usize,
+}
+
+impl Transaction {
+    /// apply all changes to the content.
+    pub fn apply(&self, content: &mut Rope) {
+        for change in self.changes.iter() {
+            change.apply(content);
+        }
+    }
+
+    /// revert all changes from the content.
+    pub fn revert(&self, content: &mut Rope) {
+        for change in self.changes.iter().rev() {
+            change.invert().apply(content);
+        }
+    }
+}
+
+/// per document undo / redo history.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    /// the transaction of the edit in progress
+    current: Option<Transaction>,
+    /// the last revision handed out
+    last_revision: usize,
+    /// when the top of the undo stack last changed
+    last_edit: Option<Instant>,
+}
+
+impl History {
+    /// the revision of the content, every transaction gets a new one.
+    pub fn revision(&self) -> usize {
+        self.undo
+            .last()
+            .map_or(0, |transaction| transaction.revision)
+    }
+
+    /// start an edit, the changes recorded until [`History::commit`] become one transaction.
+    pub fn begin(&mut self, before: ViewState) {
+        self.current = Some(Transaction {
+            changes: Vec::new(),
+            after: before.clone(),
+            before,
+            revision: 0,
+        });
+    }
+
+    /// record a change that was applied to the content.
+    ///
+    /// a change outside of [`History::begin`] / [`History::commit`] is a transaction of its own.
+    pub fn record(&mut self, change: Change) {
+        match self.current.as_mut() {
+            Some(transaction) => transaction.changes.push(change),
+            None => {
+                self.begin(ViewState::default());
+                self.record(change);
+                self.commit(ViewState::default());
+            }
+        }
+    }
+
+    /// finish the edit started by [`History::begin`].
+    ///
+    /// typing a word is grouped into the previous transaction, so it's undone in one step.
+    pub fn commit(&mut self, after: ViewState) {
+        let mut transaction = match self.current.take() {
+            Some(transaction) if !transaction.changes.is_empty() => transaction,
+            _ => return,
+        };
+        transaction.after = after;
+        self.last_revision += 1;
+        transaction.revision = self.last_revision;
+
+        let now = Instant::now();
+        let recent = self
+            .last_edit
+            .is_some_and(|time| now.duration_since(time) < GROUP_TIMEOUT);
+        self.last_edit = Some(now);
+        self.redo.clear();
+
+        if let Some(last) = self.undo.last_mut().filter(|_| recent) {
+            if let ([prev], [next]) = (last.changes.as_slice(), transaction.changes.as_slice()) {
+                if let Some(merged) = prev.merge(next) {
+                    last.changes = vec![merged];
+                    last.after = transaction.after;
+                    last.revision = transaction.revision;
+                    return;
+                }
+            }
+        }
+
+        self.undo.push(transaction);
+    }
+
+    /// the next transaction starts a new undo step, even when it could be grouped.
+    pub fn close_group(&mut self) {
+        self.last_edit = None;
+    }
+
+    /// move the newest transaction to the redo stack, the caller reverts it from the content.
+    pub fn undo(&mut self) -> Option<&Transaction> {
+        let transaction = self.undo.pop()?;
+        self.last_edit = None;
+        self.redo.push(transaction);
+        self.redo.last()
+    }
+
+    /// move the newest undone transaction back, the caller applies it to the content.
+    pub fn redo(&mut self) -> Option<&Transaction> {
+        let transaction = self.redo.pop()?;
+        self.last_edit = None;
+        self.undo.push(transaction);
+        self.undo.last()
+    }
+
+    /// write the history to `file`, tagged with the hash of the content it belongs to.
+    pub fn persist(&self, file: &Path, content: &Rope) -> AppResult<()> {
+        let mut writer = BufWriter::new(create_file(file)?);
+        writeln!(writer, "{}", UNDO_FILE_HEADER)?;
+        writeln!(writer, "{:016x}", hash_rope(content))?;
+        write_transactions(&mut writer, &self.undo)?;
+        write_transactions(&mut writer, &self.redo)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// read the history written by [`History::persist`].
+    ///
+    /// a history that was written for another content (the file changed since) is useless,
+    /// it's removed and `None` is returned.
+    pub fn restore(file: &Path, content: &Rope) -> AppResult<Option<History>> {
+        let bytes = match fs::read(file) {
+            Ok(bytes) => bytes,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        match (UndoReader { bytes: &bytes }).history(content) {
+            Ok(Some(history)) => Ok(Some(history)),
+            // outdated or broken, discard it.
+            _ => {
+                fs::remove_file(file)?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn write_transactions<W: Write>(writer: &mut W, transactions: &[Transaction]) -> AppResult<()> {
+    writeln!(writer, "{}", transactions.len())?;
+    for transaction in transactions {
+        writeln!(
+            writer,
+            "{} {}",
+            transaction.revision,
+            transaction.changes.len()
+        )?;
+        write_view(writer, &transaction.before)?;
+        write_view(writer, &transaction.after)?;
+
+        for change in transaction.changes.iter() {
+            writeln!(
+                writer,
+                "{} {} {}",
+                change.at,
+                change.removed.len(),
+                change.inserted.len()
+            )?;
+            writer.write_all(change.removed.as_bytes())?;
+            writer.write_all(change.inserted.as_bytes())?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// a view on one line: the offset, the primary index and the anchor and head of every range.
+fn write_view<W: Write>(writer: &mut W, view: &ViewState) -> AppResult<()> {
+    write!(
+        writer,
+        "{} {} {}",
+        view.offset.x,
+        view.offset.y,
+        view.selection.primary_index()
+    )?;
+    for range in view.selection.ranges() {
+        let (anchor, head) = (range.anchor, range.head);
+        write!(writer, " {} {} {} {}", anchor.x, anchor.y, head.x, head.y)?;
+    }
+    writeln!(writer)?;
+
+    Ok(())
+}
+
+/// parse the undo file written by [`History::persist`].
+struct UndoReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> UndoReader<'a> {
+    fn history(&mut self, content: &Rope) -> AppResult<Option<History>> {
+        if self.line()? != UNDO_FILE_HEADER {
+            bail!("unknown undo file");
+        }
+        if self.line()? != format!("{:016x}", hash_rope(content)) {
+            return Ok(None);
+        }
+
+        let undo = self.transactions()?;
+        let redo = self.transactions()?;
+        let last_revision = undo
+            .iter()
+            .chain(redo.iter())
+            .map(|transaction| transaction.revision)
+            .max()
+            .unwrap_or(0);
+
+        Ok(Some(History {
+            undo,
+            redo,
+            last_revision,
+            ..Default::default()
+        }))
+    }
+
+    fn line(&mut self) -> AppResult<&'a str> {
+        let end = self
+            .bytes
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .ok_or_else(|| anyhow!("unexpected end of undo file"))?;
+        let line = std::str::from_utf8(&self.bytes[..end])?;
+        self.bytes = &self.bytes[end + 1..];
+
+        Ok(line)
+    }
+
+    fn take(&mut self, len: usize) -> AppResult<&'a str> {
+        if len > self.bytes.len() {
+            bail!("unexpected end of undo file");
+        }
+        let text = std::str::from_utf8(&self.bytes[..len])?;
+        self.bytes = &self.bytes[len..];
+
+        Ok(text)
+    }
+
+    fn numbers(&mut self) -> AppResult<Vec<usize>> {
+        Ok(self
+            .line()?
+            .split(' ')
+            .map(str::parse)
+            .collect::<Result<_, _>>()?)
+    }
+
+    fn view(&mut self) -> AppResult<ViewState> {
+        let numbers = self.numbers()?;
+        let (view, ranges) = numbers.split_at(3.min(numbers.len()));
+        let ([x, y, primary], true) = (view, ranges.len() % 4 == 0) else {
+            bail!("broken view in undo file");
+        };
+
+        let ranges = ranges
+            .chunks(4)
+            .map(|range| {
+                Range::new(
+                    Position {
+                        x: range[0],
+                        y: range[1],
+                    },
+                    Position {
+                        x: range[2],
+                        y: range[3],
+                    },
+                )
+            })
+            .collect();
+
+        Ok(ViewState {
+            selection: Selection::new(ranges, *primary),
+            offset: Position { x: *x, y: *y },
+        })
+    }
+
+    fn transactions(&mut self) -> AppResult<Vec<Transaction>> {
+        let count: usize = self.line()?.parse()?;
+        let mut transactions = Vec::with_capacity(count);
+
+        for _ in 0..count {
+            let [revision, changes] = self.numbers()?[..] else {
+                bail!("broken transaction in undo file");
+            };
+
+            let mut transaction = Transaction {
+                changes: Vec::with_capacity(changes),
+                before: self.view()?,
+                after: self.view()?,
+                revision,
+            };
+
+            for _ in 0..changes {
+                let [at, removed, inserted] = self.numbers()?[..] else {
+                    bail!("broken change in undo file");
+                };
+                let removed = self.take(removed)?.to_string();
+                let inserted = self.take(inserted)?.to_string();
+                self.line()?;
+
+                transaction.changes.push(Change {
+                    at,
+                    removed,
+                    inserted,
+                });
+            }
+
+            transactions.push(transaction);
+        }
+
+        Ok(transactions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::history::{Change, History, ViewState};
+
+    fn type_str(history: &mut History, content: &mut Rope, at: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let change = Change::insert(at + i, &c.to_string());
+            change.apply(content);
+            history.record(change);
+        }
+    }
+
+    #[test]
+    fn test_group_words() {
+        let mut history = History::default();
+        let mut content = Rope::new();
+        type_str(&mut history, &mut content, 0, "hello world");
+        assert_eq!(content.to_string(), "hello world");
+
+        history.undo().unwrap().revert(&mut content);
+        assert_eq!(content.to_string(), "hello");
+        history.undo().unwrap().revert(&mut content);
+        assert_eq!(content.to_string(), "");
+        assert!(history.undo().is_none());
+        assert_eq!(history.revision(), 0);
+
+        history.redo().unwrap().apply(&mut content);
+        history.redo().unwrap().apply(&mut content);
+        assert_eq!(content.to_string(), "hello world");
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_close_group() {
+        let mut history = History::default();
+        let mut content = Rope::new();
+        type_str(&mut history, &mut content, 0, "ab");
+        history.close_group();
+        type_str(&mut history, &mut content, 2, "c");
+
+        history.undo().unwrap().revert(&mut content);
+        assert_eq!(content.to_string(), "ab");
+    }
+
+    #[test]
+    fn test_backspace_group() {
+        let mut history = History::default();
+        let mut content = Rope::from("abc");
+        for at in (0..3).rev() {
+            let change = Change::remove(at, &content.slice(at..at + 1).to_string());
+            change.apply(&mut content);
+            history.record(change);
+        }
+        assert_eq!(content.to_string(), "");
+
+        history.undo().unwrap().revert(&mut content);
+        assert_eq!(content.to_string(), "abc");
+    }
+
+    #[test]
+    fn test_revision() {
+        let mut history = History::default();
+        let mut content = Rope::new();
+        type_str(&mut history, &mut content, 0, "a");
+        let saved = history.revision();
+
+        history.begin(ViewState::default());
+        let change = Change::insert(1, "\n");
+        change.apply(&mut content);
+        history.record(change);
+        history.commit(ViewState::default());
+        assert_ne!(history.revision(), saved);
+
+        history.undo();
+        assert_eq!(history.revision(), saved);
+    }
+
+    #[test]
+    fn test_persist() {
+        let file = std::env::temp_dir().join(format!("raw-undo-{}", std::process::id()));
+        let mut history = History::default();
+        let mut content = Rope::new();
+        type_str(&mut history, &mut content, 0, "你好\nworld");
+        history.undo().unwrap().revert(&mut content);
+        history.persist(&file, &content).unwrap();
+
+        let mut restored = History::restore(&file, &content).unwrap().unwrap();
+        assert_eq!(restored.revision(), history.revision());
+        restored.redo().unwrap().apply(&mut content);
+        assert_eq!(content.to_string(), "你好\nworld");
+        while let Some(transaction) = restored.undo() {
+            transaction.revert(&mut content);
+        }
+        assert_eq!(content.to_string(), "");
+
+        // the content changed since the history was written.
+        assert!(History::restore(&file, &Rope::from("other"))
+            .unwrap()
+            .is_none());
+        assert!(!file.exists());
+    }
+}
@@ -0,0 +1,101 @@
+use ropey::Rope;
+
+/// how many lines are looked at to detect the indentation.
+const DETECT_LINES: usize = 1000;
+
+/// the indentation style of a document.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Indent {
+    Tabs,
+    Spaces(usize),
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Indent::Spaces(4)
+    }
+}
+
+impl Indent {
+    /// one level of indentation.
+    pub fn unit(&self) -> String {
+        match self {
+            Indent::Tabs => "\t".to_string(),
+            Indent::Spaces(n) => " ".repeat(*n),
+        }
+    }
+}
+
+/// detect the indentation of `content`, `None` when no line is indented or no step looks like a level.
+///
+/// tabs win when more lines start with a tab than with spaces, otherwise the width is the most
+/// common step between the indentation of consecutive lines.
+pub fn detect(content: &Rope) -> Option<Indent> {
+    let (mut tabs, mut spaces) = (0, 0);
+    let mut steps = [0usize; 9];
+    let mut prev = 0;
+    for line in content.lines().take(DETECT_LINES) {
+        let mut chars = line.chars().peekable();
+        let indent = match chars.peek() {
+            Some('\t') => {
+                tabs += 1;
+                continue;
+            }
+            Some(' ') => chars.by_ref().take_while(|c| *c == ' ').count(),
+            _ => 0,
+        };
+
+        // blank lines say nothing about the indentation.
+        if line.chars().all(char::is_whitespace) {
+            continue;
+        }
+        if indent > 0 {
+            spaces += 1;
+        }
+
+        let step = indent.abs_diff(prev);
+        if (1..steps.len()).contains(&step) {
+            steps[step] += 1;
+        }
+        prev = indent;
+    }
+
+    if tabs == 0 && spaces == 0 {
+        return None;
+    }
+    if tabs > spaces {
+        return Some(Indent::Tabs);
+    }
+
+    // on a tie the narrower step wins, closing two levels at once makes a step twice as wide.
+    let width = (1..steps.len()).rev().max_by_key(|step| steps[*step])?;
+    // every step is too wide to be one level, e.g. only lines indented by 10 spaces.
+    match steps[width] {
+        0 => None,
+        _ => Some(Indent::Spaces(width)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::indent::{detect, Indent};
+
+    #[test]
+    fn test_detect() {
+        let detect = |text: &str| detect(&Rope::from(text));
+        assert_eq!(detect("a\nb\n"), None);
+        assert_eq!(detect("a:\n\tb\n\tc\n"), Some(Indent::Tabs));
+        assert_eq!(
+            detect("a {\n  b {\n    c\n\n  }\n}\n"),
+            Some(Indent::Spaces(2))
+        );
+        assert_eq!(
+            detect("a {\n    b {\n        c\n    }\n}\n"),
+            Some(Indent::Spaces(4))
+        );
+        assert_eq!(detect("a\n          b\n          c\n"), None);
+        assert_eq!(Indent::Spaces(2).unit(), "  ");
+    }
+}
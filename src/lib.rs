@@ -2,10 +2,26 @@ extern crate core;
 
 pub mod app;
 pub mod args;
+pub mod clipboard;
+pub mod command;
+pub mod diff;
+pub mod disk;
+pub mod encoding;
 pub mod event;
 pub mod extension;
+pub mod filetype;
+pub mod history;
+pub mod indent;
+pub mod line_ending;
+pub mod line_index;
+pub mod loader;
+pub mod register;
 pub mod render;
 pub mod screen;
+pub mod selection;
+pub mod state;
+pub mod swap;
 
 pub const DEFAULT_FILENAME: &str = "未命名";
-pub const DEFAULT_FILETYPE: &str = "🤖 文件类型TODO";
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+pub const DEFAULT_FILETYPE: &str = "text";
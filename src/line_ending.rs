@@ -0,0 +1,92 @@
+use std::fmt;
+
+use ropey::{Rope, RopeSlice};
+
+/// the line endings of a document.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    /// both, every line keeps its own
+    Mixed,
+}
+
+impl LineEnding {
+    /// the line break inserted into a line of this style.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Lf | LineEnding::Mixed => "\n",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lf" | "unix" => Some(LineEnding::Lf),
+            "crlf" | "dos" => Some(LineEnding::Crlf),
+            _ => None,
+        }
+    }
+
+    /// the style of the line break at the end of `line`, `None` for the last line.
+    pub fn of(line: RopeSlice) -> Option<Self> {
+        let len = line.len_chars();
+        match (
+            len.checked_sub(2).map(|i| line.char(i)),
+            len.checked_sub(1).map(|i| line.char(i)),
+        ) {
+            (Some('\r'), Some('\n')) => Some(LineEnding::Crlf),
+            (_, Some('\n')) => Some(LineEnding::Lf),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+            LineEnding::Mixed => "Mixed",
+        })
+    }
+}
+
+/// detect the line endings of `content`, a document without line breaks is [`LineEnding::Lf`].
+pub fn detect(content: &Rope) -> LineEnding {
+    let (mut lf, mut crlf) = (false, false);
+    for line in content.lines() {
+        match LineEnding::of(line) {
+            Some(LineEnding::Crlf) => crlf = true,
+            Some(_) => lf = true,
+            None => {}
+        }
+
+        if lf && crlf {
+            return LineEnding::Mixed;
+        }
+    }
+
+    match crlf {
+        true => LineEnding::Crlf,
+        false => LineEnding::Lf,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::line_ending::{detect, LineEnding};
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect(&Rope::from("a")), LineEnding::Lf);
+        assert_eq!(detect(&Rope::from("a\nb\n")), LineEnding::Lf);
+        assert_eq!(detect(&Rope::from("a\r\nb\r\n")), LineEnding::Crlf);
+        assert_eq!(detect(&Rope::from("a\r\nb\nc")), LineEnding::Mixed);
+        assert_eq!(LineEnding::parse("CRLF"), Some(LineEnding::Crlf));
+        assert_eq!(LineEnding::Mixed.to_string(), "Mixed");
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+/// a checkpoint is kept every this many graphemes of a long line.
+pub const CHECKPOINT_GAP: usize = 256;
+
+/// how many long lines keep their checkpoints, the others are forgotten when there are more.
+const MAX_LINES: usize = 64;
+
+/// a grapheme boundary of a line.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// the grapheme index from the line start
+    pub x: usize,
+    /// the char offset from the line start
+    pub offset: usize,
+    /// the display column, `\t` expanded
+    pub column: usize,
+}
+
+/// checkpoints of the long lines, a position near the cursor is found from the closest one
+/// instead of counting from the line start.
+#[derive(Debug, Default)]
+pub struct LineIndex {
+    /// the checkpoints of line `y`, sorted
+    lines: HashMap<usize, Vec<Checkpoint>>,
+}
+
+impl LineIndex {
+    /// the last checkpoint of line `y` that `before` holds for, the line start when there's none.
+    ///
+    /// `before` holds for the checkpoints up to a point and not after it, e.g. `x <= 100`.
+    pub fn last_before<F>(&self, y: usize, before: F) -> Checkpoint
+    where
+        F: Fn(&Checkpoint) -> bool,
+    {
+        let Some(checkpoints) = self.lines.get(&y) else {
+            return Checkpoint::default();
+        };
+        match checkpoints.partition_point(before) {
+            0 => Checkpoint::default(),
+            n => checkpoints[n - 1],
+        }
+    }
+
+    /// remember the checkpoints found on line `y`.
+    pub fn insert(&mut self, y: usize, found: Vec<Checkpoint>) {
+        if found.is_empty() {
+            return;
+        }
+        if !self.lines.contains_key(&y) && self.lines.len() >= MAX_LINES {
+            self.lines.clear();
+        }
+
+        let checkpoints = self.lines.entry(y).or_default();
+        checkpoints.extend(found);
+        checkpoints.sort_unstable_by_key(|checkpoint| checkpoint.x);
+        checkpoints.dedup_by_key(|checkpoint| checkpoint.x);
+    }
+
+    /// line `y` changed from the char `offset` on, the checkpoints from there on are wrong.
+    ///
+    /// a change that adds or removes a line break moves the lines, see [`LineIndex::clear`].
+    pub fn edited(&mut self, y: usize, offset: usize) {
+        if let Some(checkpoints) = self.lines.get_mut(&y) {
+            checkpoints.retain(|checkpoint| checkpoint.offset < offset);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::line_index::{Checkpoint, LineIndex};
+
+    #[test]
+    fn test_line_index() {
+        let at = |x, offset| Checkpoint {
+            x,
+            offset,
+            column: x * 2,
+        };
+        let mut index = LineIndex::default();
+        assert_eq!(index.last_before(0, |c| c.x <= 500), Checkpoint::default());
+
+        index.insert(0, vec![at(512, 600), at(256, 300)]);
+        index.insert(0, vec![at(256, 300), at(768, 900)]);
+        assert_eq!(index.last_before(0, |c| c.x <= 255), Checkpoint::default());
+        assert_eq!(index.last_before(0, |c| c.x <= 600), at(512, 600));
+        assert_eq!(index.last_before(0, |c| c.offset <= 300), at(256, 300));
+        assert_eq!(index.last_before(0, |c| c.column <= 10_000), at(768, 900));
+        assert_eq!(index.last_before(1, |c| c.x <= 600), Checkpoint::default());
+
+        index.edited(0, 600);
+        assert_eq!(index.last_before(0, |c| c.x <= 10_000), at(256, 300));
+        index.clear();
+        assert_eq!(
+            index.last_before(0, |c| c.x <= 10_000),
+            Checkpoint::default()
+        );
+    }
+}
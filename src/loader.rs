@@ -0,0 +1,191 @@
+//! read big files (or stdin) on a background thread, the document shows what's read so far.
+
+use std::io;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError};
+use std::thread;
+
+use encoding_rs::{Decoder, DecoderResult};
+
+/// files from this size on are loaded in the background.
+pub const LARGE_FILE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// how much is read at once.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// how many chunks can wait for the document, the thread stops reading until they are taken.
+const PENDING_CHUNKS: usize = 8;
+
+/// what the thread sends: the bytes read so far and the text decoded from them.
+type Chunk = Result<(u64, String), String>;
+
+/// what [`Loader::read`] got from the thread.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Read {
+    /// text to append to the document
+    Text(String),
+    /// nothing new yet
+    Pending,
+    /// the whole file is read
+    Done,
+    /// reading or decoding failed, the document only has the part before it
+    Failed(String),
+}
+
+/// a file being read on a background thread.
+#[derive(Debug)]
+pub struct Loader {
+    receiver: Receiver<Chunk>,
+    /// `None` when it isn't known, e.g. a pipe
+    size: Option<u64>,
+    read: u64,
+    failed: bool,
+}
+
+impl Loader {
+    /// start reading `reader` of `size` bytes, the text is decoded with `decoder`.
+    ///
+    /// without a decoder every byte becomes a char from `\u{0}` to `\u{ff}`, see [`crate::render::document::Document::binary`].
+    pub fn spawn<R>(reader: R, size: Option<u64>, decoder: Option<Decoder>) -> Self
+    where
+        R: io::Read + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(PENDING_CHUNKS);
+        thread::spawn(move || load(reader, decoder, sender));
+
+        Self {
+            receiver,
+            size,
+            read: 0,
+            failed: false,
+        }
+    }
+
+    /// take the next chunk without waiting for it.
+    pub fn read(&mut self) -> Read {
+        if self.failed {
+            return Read::Pending;
+        }
+
+        match self.receiver.try_recv() {
+            Ok(Ok((read, text))) => {
+                self.read = read;
+                Read::Text(text)
+            }
+            Ok(Err(err)) => {
+                self.failed = true;
+                Read::Failed(err)
+            }
+            Err(TryRecvError::Empty) => Read::Pending,
+            Err(TryRecvError::Disconnected) => Read::Done,
+        }
+    }
+
+    /// how much of the file is read in percent, `None` when the size isn't known.
+    pub fn progress(&self) -> Option<u8> {
+        let size = self.size?;
+        Some((self.read * 100 / size.max(1)).min(100) as u8)
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+}
+
+/// the loop of the thread, it ends when the file is read or the document is gone.
+///
+/// a pipe returns what the producer has written so far, it's sent right away.
+fn load<R: io::Read>(mut reader: R, mut decoder: Option<Decoder>, sender: SyncSender<Chunk>) {
+    let mut buf = vec![0; CHUNK_SIZE];
+    let mut read = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                let _ = sender.send(Err(err.to_string()));
+                return;
+            }
+        };
+        read += n as u64;
+
+        let text = match decoder.as_mut() {
+            Some(decoder) => match decode(decoder, &buf[..n], n == 0) {
+                Some(text) => Ok((read, text)),
+                None => Err(format!("not valid {}", decoder.encoding().name())),
+            },
+            None => Ok((read, buf[..n].iter().map(|byte| *byte as char).collect())),
+        };
+
+        let failed = text.is_err();
+        if sender.send(text).is_err() || failed || n == 0 {
+            return;
+        }
+    }
+}
+
+/// decode a chunk, a char cut off at its end is kept by the decoder for the next one.
+fn decode(decoder: &mut Decoder, bytes: &[u8], last: bool) -> Option<String> {
+    let len = decoder.max_utf8_buffer_length_without_replacement(bytes.len())?;
+    let mut text = String::with_capacity(len);
+    match decoder.decode_to_string_without_replacement(bytes, &mut text, last) {
+        (DecoderResult::InputEmpty, _) => Some(text),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use encoding_rs::{UTF_8, WINDOWS_1252};
+
+    use crate::loader::{Loader, Read, CHUNK_SIZE};
+
+    fn read_all(mut loader: Loader) -> (String, Read) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut text = String::new();
+        while Instant::now() < deadline {
+            match loader.read() {
+                Read::Text(chunk) => text.push_str(&chunk),
+                Read::Pending => std::thread::sleep(Duration::from_millis(1)),
+                read => return (text, read),
+            }
+        }
+        panic!("loading timed out");
+    }
+
+    #[test]
+    fn test_load() {
+        let path = std::env::temp_dir().join(format!("raw-loader-{}.txt", std::process::id()));
+        // the chunks end in the middle of a char.
+        let text = format!("a{}", "你".repeat(CHUNK_SIZE / 3 + 1));
+        std::fs::write(&path, &text).unwrap();
+
+        let open = || std::fs::File::open(&path).unwrap();
+        let size = text.len() as u64;
+        let loader = Loader::spawn(
+            open(),
+            Some(size),
+            Some(UTF_8.new_decoder_with_bom_removal()),
+        );
+        assert_eq!(loader.progress(), Some(0));
+        assert_eq!(read_all(loader), (text, Read::Done));
+
+        let loader = Loader::spawn(open(), Some(size), None);
+        let (bytes, read) = read_all(loader);
+        assert_eq!(read, Read::Done);
+        assert_eq!(bytes.chars().count() as u64, size);
+
+        std::fs::write(&path, b"ab\xff").unwrap();
+        let loader = Loader::spawn(open(), None, Some(UTF_8.new_decoder_with_bom_removal()));
+        assert_eq!(loader.progress(), None);
+        assert_eq!(
+            read_all(loader),
+            (String::new(), Read::Failed("not valid UTF-8".to_string()))
+        );
+        let loader = Loader::spawn(open(), Some(3), Some(WINDOWS_1252.new_decoder()));
+        assert_eq!(read_all(loader), ("abÿ".to_string(), Read::Done));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
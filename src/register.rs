@@ -0,0 +1,23 @@
+use std::collections::HashMap;
+
+/// the register used when no other register is selected.
+pub const DEFAULT_REGISTER: char = '"';
+
+/// the register pasted from the system clipboard, it holds the text last copied there.
+pub const CLIPBOARD_REGISTER: char = '+';
+
+/// named registers that hold cut / copied text, shared by all documents.
+#[derive(Default, Debug)]
+pub struct Registers {
+    registers: HashMap<char, String>,
+}
+
+impl Registers {
+    pub fn get(&self, name: char) -> Option<&str> {
+        self.registers.get(&name).map(String::as_str)
+    }
+
+    pub fn set(&mut self, name: char, text: String) {
+        self.registers.insert(name, text);
+    }
+}
@@ -4,6 +4,7 @@ use tui::layout::Rect;
 
 pub mod banner;
 pub mod document;
+pub mod hex;
 pub mod message;
 pub mod status_line;
 pub mod switcher;
@@ -23,7 +23,7 @@ impl Render for Banner {
         "banner".to_string()
     }
 
-    fn render(&mut self, ctx: AppCtx, buf: &mut Buffer, area: Rect) {
+    fn render(&mut self, _ctx: AppCtx, buf: &mut Buffer, area: Rect) {
         let chunks = Layout::default()
             .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
             .direction(Direction::Vertical)
@@ -36,8 +36,7 @@ impl Render for Banner {
             .map(|line| Paragraph::new(line.get_string()).alignment(Alignment::Center))
             .collect();
 
-        let mut y = chunk.y;
-        for p in contents {
+        for (y, p) in (chunk.y..).zip(contents) {
             if y >= chunk.height {
                 // check for overflow
                 return;
@@ -51,7 +50,6 @@ impl Render for Banner {
                 },
                 buf,
             );
-            y += 1;
         }
     }
 }
@@ -76,4 +74,4 @@ mod tests {
         println!("{}", contents.len());
         println!("{:?}", contents)
     }
-}
\ No newline at end of file
+}
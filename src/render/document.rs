@@ -1,21 +1,98 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufWriter, Read as _, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use encoding_rs::{Encoding, UTF_8};
 use ropey::{Rope, RopeSlice};
 use tui::buffer::Buffer;
 use tui::layout::Rect;
-use tui::style::Style;
+use tui::style::{Color, Modifier, Style};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
 use crate::app::{AppCtx, AppResult};
-use crate::extension::rope::RopeSliceEx;
+use crate::disk::{DiskChange, DiskState};
+use crate::encoding;
+use crate::extension::graphemes_ex::{
+    next_grapheme_boundary, nth_next_grapheme_boundary, prev_grapheme_boundary, RopeGraphemes,
+};
+use crate::extension::rope::{Line, RopeSliceEx};
+use crate::extension::word::{self, WordKind};
+use crate::filetype::{self, FileType};
+use crate::history::{Change, History, Transaction, ViewState};
+use crate::indent::{self, Indent};
+use crate::line_ending::{self, LineEnding};
+use crate::line_index::{Checkpoint, LineIndex, CHECKPOINT_GAP};
+use crate::loader::{self, Loader, LARGE_FILE_SIZE};
+use crate::render::hex::{self, BYTES_PER_ROW};
 use crate::render::Render;
-use crate::{DEFAULT_FILENAME, DEFAULT_FILETYPE};
+use crate::screen::Position;
+use crate::selection::{Range, Selection};
+use crate::state::state_file;
+use crate::swap;
+use crate::{DEFAULT_FILENAME, DEFAULT_FILETYPE, DEFAULT_TAB_WIDTH};
+
+/// the directory in [`crate::state::state_dir`] that keeps the undo history of documents.
+const UNDO_DIR: &str = "undo";
+
+/// how much of a large file is read to detect its encoding.
+const HEAD_SIZE: u64 = 64 * 1024;
+
+/// a line with more chars is only converted for the part on the screen.
+const LONG_LINE: usize = 10_000;
+
+/// how many columns of a long line are converted, it's wider than any screen.
+const WINDOW_WIDTH: usize = 1024;
+
+/// background of selected text.
+pub const SELECTION_BG: Color = Color::Rgb(68, 71, 90);
 
 /// the document
 #[derive(Debug)]
 pub struct Document {
     pub content: Rope,
     name: String,
-    filetype: String,
+    filetype: Option<&'static FileType>,
+    /// how the lines are indented
+    indent: Indent,
+    /// a `\t` expands to the next multiple of this
+    tab_width: usize,
+    /// the line breaks of the content, kept as they are in the file
+    line_ending: LineEnding,
+    /// the encoding of the file, the content is written back in it
+    encoding: &'static Encoding,
+    /// the file starts with a byte order mark
+    bom: bool,
+    /// a binary file shown as hex, every char of the content is a byte
+    binary: bool,
+    /// a big file, the features that go through the whole content are off
+    large: bool,
+    /// reads the file in the background until it's loaded
+    loader: Option<Loader>,
+    /// every change of the content is recorded here
+    history: History,
+    /// the revision written to disk by the last save
+    saved_revision: usize,
+    /// the file as it was last read or written, `None` when it isn't on disk
+    disk: Option<DiskState>,
+    /// the swap file the unsaved changes are written to, see [`Document::write_swap`]
+    swap: Option<PathBuf>,
+    /// the revision in the swap file, `None` when it isn't written
+    swapped_revision: Option<usize>,
+    /// the unsaved changes of a session that crashed, found in the swap file
+    recovery: Option<String>,
+    /// another raw that is editing the file, it owns the swap file
+    editing_pid: Option<u32>,
+    /// why the swap file of an earlier session can't be read, it's left as it is
+    swap_error: Option<String>,
+    /// a scratch buffer (e.g. a diff) that isn't a file, it's never edited or saved
+    read_only: bool,
+    /// where the graphemes of the long lines are, so the cursor isn't found from the line start
+    line_index: RefCell<LineIndex>,
 }
 
 impl Render for Document {
@@ -28,48 +105,597 @@ impl Render for Document {
             return;
         }
 
-        for (x, line) in self
-            .content
-            .lines()
-            .skip(ctx.cal_offset_y())
+        let offset_y = ctx.cal_offset_y().min(self.content.len_lines());
+        for (x, y) in (offset_y..self.content.len_lines())
             .take(area.height as usize)
             .enumerate()
         {
-            if x >= area.height as usize {
-                return;
-            };
+            let line = self.row_window(y, ctx.offset.x);
+            buf.set_string(0, x as u16, line.render(ctx.offset.x), Style::default());
 
-            // line.
-            // todo 是不是太暴力了.
-            buf.set_string(
-                0,
-                x as u16,
-                line.to_line().render(ctx.offset.x),
-                Style::default(),
-            );
+            for (i, range) in ctx.selection.ranges().iter().enumerate() {
+                if let Some(span) = range.line_span(y) {
+                    highlight(&line, span, ctx.offset.x, buf, area, x as u16);
+                }
+                // the terminal shows the primary cursor, draw the others.
+                if i != ctx.selection.primary_index() && range.head.y == y {
+                    mark_cursor(&line, range.head.x, ctx.offset.x, buf, area, x as u16);
+                }
+            }
         }
     }
 }
 
+/// draw a cursor that isn't the terminal cursor at grapheme `x` of a rendered line.
+fn mark_cursor(line: &Line, x: usize, offset_x: usize, buf: &mut Buffer, area: Rect, y: u16) {
+    if x < offset_x {
+        return;
+    }
+
+    let left = line.get_offset(x) - line.get_offset(offset_x);
+    if left >= area.width as usize {
+        return;
+    }
+
+    let width = line.grapheme_width(x).max(1);
+    buf.set_style(
+        Rect {
+            x: area.x + left as u16,
+            y: area.y + y,
+            width: width.min(area.width as usize - left) as u16,
+            height: 1,
+        },
+        Style::default().add_modifier(Modifier::REVERSED),
+    );
+}
+
+/// highlight the selected graphemes `span` of a rendered line.
+fn highlight(
+    line: &Line,
+    (from, to): (usize, Option<usize>),
+    offset_x: usize,
+    buf: &mut Buffer,
+    area: Rect,
+    y: u16,
+) {
+    let continues = to.is_none();
+    let from = from.max(offset_x);
+    let to = to.unwrap_or(line.start + line.str_list.len()).max(from);
+
+    let origin = line.get_offset(offset_x);
+    let left = line.get_offset(from) - origin;
+    let mut right = line.get_offset(to) - origin;
+    if continues {
+        // the selection continues on the next line, highlight the line break too.
+        right += 1;
+    }
+    if left >= right || left >= area.width as usize {
+        return;
+    }
+
+    buf.set_style(
+        Rect {
+            x: area.x + left as u16,
+            y: area.y + y,
+            width: (right.min(area.width as usize) - left) as u16,
+            height: 1,
+        },
+        Style::default().bg(SELECTION_BG),
+    );
+}
+
 impl Document {
     pub fn from(content: Rope, filepath: &str) -> Self {
+        let filetype = filetype::detect(filepath);
+        let indent = indent::detect(&content)
+            .or_else(|| filetype.map(FileType::default_indent))
+            .unwrap_or_default();
+        let line_ending = line_ending::detect(&content);
+
         Self {
             content,
             name: filepath.to_string(),
-            filetype: DEFAULT_FILETYPE.to_string(),
+            filetype,
+            indent,
+            tab_width: DEFAULT_TAB_WIDTH,
+            line_ending,
+            encoding: UTF_8,
+            bom: false,
+            binary: false,
+            large: false,
+            loader: None,
+            history: History::default(),
+            saved_revision: 0,
+            disk: None,
+            swap: None,
+            swapped_revision: None,
+            recovery: None,
+            editing_pid: None,
+            swap_error: None,
+            read_only: false,
+            line_index: RefCell::default(),
+        }
+    }
+
+    /// a read-only scratch buffer, `title` is only shown and never used as a path.
+    pub fn scratch(content: Rope, title: &str) -> Self {
+        Self {
+            read_only: true,
+            ..Self::from(content, title)
         }
     }
 
+    /// an empty document for a file that doesn't exist yet, it's created on the first save.
+    ///
+    /// the swap file is there from the start, a crash before that save would lose the most.
+    pub fn new_file(filepath: &str) -> Self {
+        let mut doc = Self::from(Rope::new(), filepath);
+        doc.open_swap();
+        doc
+    }
+
+    /// a binary document, `content` holds a char from `\u{0}` to `\u{ff}` for every byte.
+    pub fn binary(content: Rope, filepath: &str) -> Self {
+        Self {
+            binary: true,
+            ..Self::from(content, filepath)
+        }
+    }
+
+    /// open a file, its encoding is detected, see [`encoding::detect`].
+    ///
+    /// a binary file is opened in hex, see [`encoding::is_binary`].
     pub fn open(filepath: &str) -> AppResult<Self> {
-        let reader = std::fs::File::open(filepath)?;
+        Self::open_with(filepath, None)
+    }
+
+    /// open a file and decode it with `encoding`, or the detected one when it's `None`.
+    ///
+    /// the undo history and the swap file of the earlier sessions are looked for, see [`Document::read`].
+    pub fn open_with(filepath: &str, encoding: Option<&'static Encoding>) -> AppResult<Self> {
+        let mut doc = Self::read(filepath, encoding)?;
+        if !doc.large {
+            doc.restore_history();
+            doc.open_swap();
+        }
+        Ok(doc)
+    }
+
+    /// read a file and decode it with `encoding`, or the detected one when it's `None`.
+    ///
+    /// it's only the file, e.g. to reload it or to diff it, the state of the earlier sessions isn't touched.
+    /// a file from [`LARGE_FILE_SIZE`] on is read in the background, see [`Document::open_large`].
+    pub fn read(filepath: &str, encoding: Option<&'static Encoding>) -> AppResult<Self> {
+        if fs::metadata(filepath)?.len() >= LARGE_FILE_SIZE {
+            return Self::open_large(filepath, encoding);
+        }
+
+        let bytes = fs::read(filepath)?;
+        if encoding.is_none() && encoding::is_binary(&bytes) {
+            let content: String = bytes.iter().map(|byte| *byte as char).collect();
+            let mut doc = Document::binary(Rope::from(content), filepath);
+            doc.disk = DiskState::from_bytes(Path::new(filepath), &bytes).ok();
+            return Ok(doc);
+        }
 
-        let content = Rope::from_reader(reader)?;
+        let (encoding, bom) = match encoding {
+            Some(encoding) => {
+                let bom = Encoding::for_bom(&bytes).filter(|(detected, _)| *detected == encoding);
+                (encoding, bom.map_or(0, |(_, bom)| bom))
+            }
+            None => encoding::detect(&bytes),
+        };
+        let content = encoding::decode(&bytes[bom..], encoding)?;
 
-        Ok(Document::from(content, filepath))
+        let mut doc = Document::from(Rope::from(content), filepath);
+        doc.encoding = encoding;
+        doc.bom = bom > 0;
+        doc.disk = DiskState::from_bytes(Path::new(filepath), &bytes).ok();
+        Ok(doc)
     }
 
-    pub fn default() -> Self {
-        Self::from(Default::default(), DEFAULT_FILENAME)
+    /// open a big file, it's read on a background thread and appended by [`Document::poll_loader`].
+    ///
+    /// the encoding is detected from the head of the file, it can't be edited until it's loaded.
+    pub fn open_large(filepath: &str, encoding: Option<&'static Encoding>) -> AppResult<Self> {
+        let size = fs::metadata(filepath)?.len();
+        let mut head = Vec::new();
+        fs::File::open(filepath)?
+            .take(HEAD_SIZE)
+            .read_to_end(&mut head)?;
+
+        let binary = encoding.is_none() && encoding::is_binary(&head);
+        // a char cut off at the end of the head is still UTF-8.
+        let head = match std::str::from_utf8(&head) {
+            Err(err) if err.error_len().is_none() => &head[..err.valid_up_to()],
+            _ => &head[..],
+        };
+        let (encoding, bom) = match encoding {
+            Some(encoding) => (
+                encoding,
+                Encoding::for_bom(head).is_some_and(|(detected, _)| detected == encoding),
+            ),
+            None => {
+                let (encoding, bom) = encoding::detect(head);
+                (encoding, bom > 0)
+            }
+        };
+
+        let decoder = match binary {
+            true => None,
+            false => Some(encoding.new_decoder_with_bom_removal()),
+        };
+        let mut doc = Document::from(Rope::new(), filepath);
+        doc.encoding = encoding;
+        doc.bom = bom;
+        doc.binary = binary;
+        doc.large = true;
+        doc.disk = DiskState::read(Path::new(filepath), false).ok();
+        doc.loader = Some(Loader::spawn(
+            fs::File::open(filepath)?,
+            Some(size),
+            decoder,
+        ));
+        Ok(doc)
+    }
+
+    /// an unnamed document read from stdin (`raw -`), it fills in while the producer is still writing.
+    ///
+    /// the text has to be UTF-8, a pipe can't be read twice to detect the encoding.
+    pub fn stdin() -> Self {
+        Self::from_reader(io::stdin())
+    }
+
+    /// an unnamed document read from `reader` of unknown size, see [`Document::stdin`].
+    pub fn from_reader<R>(reader: R) -> Self
+    where
+        R: io::Read + Send + 'static,
+    {
+        let decoder = UTF_8.new_decoder_with_bom_removal();
+        Self {
+            loader: Some(Loader::spawn(reader, None, Some(decoder))),
+            ..Self::default()
+        }
+    }
+
+    /// append what the loader has read, it stops after `budget` so the screen keeps up.
+    ///
+    /// returns a message when the loading is done or failed.
+    pub fn poll_loader(&mut self, budget: Duration) -> Option<String> {
+        let loader = self.loader.as_mut()?;
+        let deadline = Instant::now() + budget;
+        while Instant::now() < deadline {
+            match loader.read() {
+                loader::Read::Text(text) => {
+                    let detect = self.content.len_chars() == 0;
+                    self.content.append(Rope::from(text));
+                    self.line_index.get_mut().clear();
+                    // the style comes from the first chunk, the whole file takes too long.
+                    if detect && !self.binary {
+                        self.indent = indent::detect(&self.content)
+                            .or_else(|| self.filetype.map(FileType::default_indent))
+                            .unwrap_or_default();
+                        self.line_ending = line_ending::detect(&self.content);
+                    }
+                }
+                loader::Read::Pending => return None,
+                loader::Read::Done => {
+                    self.loader = None;
+                    return Some(format!("\"{}\" loaded", self.name));
+                }
+                loader::Read::Failed(err) => {
+                    return Some(format!(
+                        "\"{}\" load failed, it's read-only: {}",
+                        self.name, err
+                    ));
+                }
+            }
+        }
+        None
+    }
+
+    /// the file is read completely, it can be edited and saved.
+    pub fn is_loaded(&self) -> bool {
+        self.loader.is_none()
+    }
+
+    /// how much of the file is loaded in percent, `None` when it's loaded or its size isn't known.
+    pub fn load_progress(&self) -> Option<u8> {
+        self.loader.as_ref().and_then(Loader::progress)
+    }
+
+    /// read the file again and decode it with `encoding`, the unsaved changes are lost.
+    pub fn reopen(&mut self, encoding: &'static Encoding) -> AppResult<()> {
+        if self.read_only {
+            bail!("read-only buffer");
+        }
+        let doc = Self::read(&self.name, Some(encoding))?;
+        self.replace_with(doc);
+        Ok(())
+    }
+
+    /// has the file changed on disk since it was read or saved? a change is only reported once.
+    ///
+    /// a file that is still loading isn't checked, the loader reads it.
+    pub fn check_disk(&mut self) -> DiskChange {
+        let disk = match self.disk.as_mut() {
+            Some(disk) if self.loader.is_none() => disk,
+            _ => return DiskChange::Unchanged,
+        };
+
+        let change = disk.check(Path::new(&self.name));
+        match change {
+            DiskChange::Changed(state) => self.disk = Some(state),
+            DiskChange::Deleted => self.disk = None,
+            DiskChange::Unchanged => {}
+        }
+        change
+    }
+
+    /// read the file again with the same encoding, the unsaved changes are lost.
+    ///
+    /// the new content replaces the old one as an undo step, so the reload can be undone.
+    /// a file that became large or binary is opened anew without the history.
+    pub fn reload(&mut self, before: ViewState) -> AppResult<()> {
+        if self.read_only {
+            bail!("read-only buffer");
+        }
+        let encoding = (!self.binary).then_some(self.encoding);
+        let doc = Self::read(&self.name, encoding)?;
+        if doc.large || self.large || doc.binary != self.binary {
+            self.replace_with(doc);
+            return Ok(());
+        }
+
+        self.replace_content(before, &doc.content.to_string());
+        self.indent = doc.indent;
+        self.line_ending = doc.line_ending;
+        self.encoding = doc.encoding;
+        self.bom = doc.bom;
+        self.disk = doc.disk;
+        self.saved_revision = self.history.revision();
+        Ok(())
+    }
+
+    /// `doc` read from the same file takes the place of this document, the swap file stays its own.
+    ///
+    /// a large file has no swap file, the one written so far is removed.
+    fn replace_with(&mut self, mut doc: Document) {
+        doc.tab_width = self.tab_width;
+        match doc.large {
+            true => self.remove_swap(),
+            false => {
+                doc.swap = self.swap.take();
+                doc.swapped_revision = self.swapped_revision;
+                doc.recovery = self.recovery.take();
+                doc.editing_pid = self.editing_pid;
+                doc.swap_error = self.swap_error.take();
+            }
+        }
+        *self = doc;
+    }
+
+    /// replace the whole content as an undo step of its own.
+    fn replace_content(&mut self, before: ViewState, text: &str) {
+        self.history.close_group();
+        self.history.begin(before.clone());
+        self.remove_range(0, self.content.len_chars());
+        self.insert_at(0, text);
+        self.history.commit(before);
+        self.history.close_group();
+    }
+
+    /// look for the swap file of an earlier session, see [`Document::recovery`] and [`Document::editing_pid`].
+    ///
+    /// a large file has no swap file, writing it would take too long.
+    fn open_swap(&mut self) {
+        let file = match swap::swap_file(Path::new(&self.name)) {
+            None => return,
+            Some(file) => file,
+        };
+
+        match swap::read(&file) {
+            // another document of this raw has the file open, e.g. `raw a a`, the swap file is its.
+            Ok(Some(found)) if found.pid == std::process::id() => return,
+            Ok(Some(found)) if swap::is_running(found.pid) => {
+                // the swap file is theirs, this document doesn't write one.
+                self.editing_pid = Some(found.pid);
+                return;
+            }
+            Ok(Some(found)) if self.content != found.content.as_str() => {
+                self.recovery = Some(found.content);
+            }
+            // the same as the file, there is nothing to recover.
+            Ok(Some(_)) => {
+                let _ = fs::remove_file(&file);
+            }
+            // e.g. a newer format, it's kept for the raw that can read it and never overwritten.
+            Err(err) => {
+                self.swap_error = Some(format!(
+                    "swap file {} can't be read ({}), it's left as it is",
+                    file.display(),
+                    err
+                ));
+                return;
+            }
+            Ok(None) => {}
+        }
+        self.swap = Some(file);
+    }
+
+    /// write the unsaved changes to the swap file, once there are none it's removed.
+    ///
+    /// it's best effort, a failed write is tried again on the next call.
+    /// nothing is written while the swap file of a crashed session waits to be recovered.
+    pub fn write_swap(&mut self) {
+        let file = match &self.swap {
+            Some(file) if self.recovery.is_none() => file,
+            _ => return,
+        };
+
+        let revision = self.history.revision();
+        if !self.is_modified() {
+            self.remove_swap();
+        } else if self.swapped_revision != Some(revision)
+            && swap::write(file, &self.content).is_ok()
+        {
+            self.swapped_revision = Some(revision);
+        }
+    }
+
+    /// remove the swap file written by this document, e.g. it's saved or closed.
+    ///
+    /// the swap file of a crashed session is kept until it's recovered or discarded.
+    pub fn remove_swap(&mut self) {
+        if let (Some(file), Some(_)) = (&self.swap, self.swapped_revision) {
+            let _ = fs::remove_file(file);
+        }
+        self.swapped_revision = None;
+    }
+
+    /// remove the swap file of the crashed session without recovering it.
+    pub fn discard_recovery(&mut self) {
+        if let (Some(file), Some(_)) = (&self.swap, self.recovery.take()) {
+            let _ = fs::remove_file(file);
+        }
+    }
+
+    /// the content of the swap file of a session that crashed, it has changes that were never saved.
+    pub fn recovery(&self) -> Option<&str> {
+        self.recovery.as_deref()
+    }
+
+    /// replace the content with the one of the crashed session as an undo step, it isn't saved yet.
+    ///
+    /// returns whether there was something to recover.
+    pub fn recover(&mut self, before: ViewState) -> bool {
+        match self.recovery.take() {
+            Some(text) => {
+                self.replace_content(before, &text);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// the pid of another raw that is editing the file.
+    pub fn editing_pid(&self) -> Option<u32> {
+        self.editing_pid
+    }
+
+    /// why the swap file of an earlier session can't be read, see [`Document::open_swap`].
+    pub fn swap_error(&self) -> Option<&str> {
+        self.swap_error.as_deref()
+    }
+
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// the name of the encoding shown to the user, e.g. `UTF-8 BOM`.
+    pub fn encoding_name(&self) -> String {
+        if self.binary {
+            return "binary".to_string();
+        }
+
+        match self.bom {
+            true => format!("{} BOM", self.encoding.name()),
+            false => self.encoding.name().to_string(),
+        }
+    }
+
+    /// write the content back to the file.
+    ///
+    /// the content goes to a temp file next to the target first and is then renamed over it,
+    /// so a crash while writing can never truncate the original. the mode bits of the original are kept.
+    pub fn save(&mut self) -> AppResult<()> {
+        if self.read_only {
+            bail!("read-only buffer");
+        }
+        if self.name == DEFAULT_FILENAME {
+            bail!("no file name");
+        }
+        match &self.loader {
+            Some(loader) if loader.is_failed() => bail!("the file isn't loaded completely"),
+            Some(_) => bail!("still loading"),
+            None => {}
+        }
+
+        // write through symlinks instead of replacing them.
+        let path = fs::canonicalize(&self.name).unwrap_or_else(|_| PathBuf::from(&self.name));
+        let tmp = tmp_path(&path);
+
+        let result = self.write_tmp(&path, &tmp);
+        match result {
+            Ok(_) => {
+                self.saved_revision = self.history.revision();
+                self.disk = DiskState::read(&path, !self.large).ok();
+                self.persist_history();
+                self.remove_swap();
+                self.discard_recovery();
+                if self.editing_pid.is_none() && !self.large {
+                    // a new file was named after its path as it was typed, now it has a canonical one.
+                    self.swap = swap::swap_file(&path);
+                }
+            }
+            Err(_) => {
+                let _ = fs::remove_file(&tmp);
+            }
+        }
+        result
+    }
+
+    /// load the undo history of the last sessions, if it was written for the current content.
+    fn restore_history(&mut self) {
+        let file = match state_file(UNDO_DIR, Path::new(&self.name)) {
+            None => return,
+            Some(file) => file,
+        };
+
+        if let Ok(Some(history)) = History::restore(&file, &self.content) {
+            self.history = history;
+            self.saved_revision = self.history.revision();
+        }
+    }
+
+    /// keep the undo history for the next session, it's best effort and never fails a save.
+    ///
+    /// a large file has no history across sessions, it would hash the whole content.
+    fn persist_history(&self) {
+        if self.large {
+            return;
+        }
+        if let Some(file) = state_file(UNDO_DIR, Path::new(&self.name)) {
+            let _ = self.history.persist(&file, &self.content);
+        }
+    }
+
+    fn write_tmp(&self, path: &Path, tmp: &Path) -> AppResult<()> {
+        let permissions = fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions());
+
+        let file = fs::File::create(tmp)?;
+        let mut writer = BufWriter::new(file);
+        if self.binary {
+            let bytes: Vec<u8> = self.content.chars().map(|c| c as u8).collect();
+            writer.write_all(&bytes)?;
+        } else if self.encoding == UTF_8 && !self.bom {
+            self.content.write_to(&mut writer)?;
+        } else {
+            let text = self.content.to_string();
+            writer.write_all(&encoding::encode(&text, self.encoding, self.bom)?)?;
+        }
+        writer.flush()?;
+
+        let file = writer.into_inner()?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.sync_all()?;
+
+        fs::rename(tmp, path)?;
+        Ok(())
     }
 
     /// get content.
@@ -79,26 +705,41 @@ impl Document {
 
     /// How many lines are in the current document
     pub fn len(&self) -> usize {
+        if self.binary {
+            // the last row has room to append a byte, it's a row of its own when the others are full.
+            return self.content.len_chars() / BYTES_PER_ROW + 1;
+        }
+
         self.content.len_lines()
     }
 
     /// get line len.
     pub fn line_len(&self, index: usize) -> usize {
+        self.line_len_at_most(index, usize::MAX)
+    }
+
+    /// like [`Document::line_len`], but a long line is only counted up to `max` graphemes.
+    pub fn line_len_at_most(&self, index: usize, max: usize) -> usize {
         if index >= self.len() {
             return 0;
         }
+        if self.binary {
+            // the cursor is on a byte, only the last row has a position after its bytes.
+            let bytes = self.row_bytes(index).len();
+            let len = match index + 1 == self.len() {
+                true => bytes,
+                false => bytes - 1,
+            };
+            return len.min(max);
+        }
 
         let slice = self.content.line(index);
-
-        let line_len = slice.len_word_boundary();
-
-        // 如果这一行没有数据，或者没有换行,直接返回usize
-        let lines_count = slice.len_lines();
-        if line_len == 0 || lines_count == 0 || lines_count == 1 {
-            return line_len;
+        if slice.len_chars() > LONG_LINE {
+            return self.seek(index, Seek::Grapheme(max)).x;
         }
-
-        line_len - 1
+        RopeGraphemes::new(slice.slice(..text_len(slice)))
+            .take(max)
+            .count()
     }
 
     /// 获取当前行的长度,使用width获取
@@ -108,8 +749,16 @@ impl Document {
         if index >= self.len() {
             return 0;
         }
+        if self.binary {
+            return self.row_to_line(index).width;
+        }
 
         let slice = self.content.line(index);
+        // measuring a long line would go through all of it on every frame, its chars are close enough.
+        if slice.len_chars() > LONG_LINE {
+            return slice.len_chars();
+        }
+
         let mut raw_width = 0;
         for str in slice.get_string().graphemes(true) {
             raw_width += str.width();
@@ -118,7 +767,7 @@ impl Document {
     }
 
     /// get line by index.
-    pub fn line(&self, index: usize) -> RopeSlice {
+    pub fn line(&self, index: usize) -> RopeSlice<'_> {
         if let Some(line) = self.content.get_line(index) {
             line
         } else {
@@ -131,13 +780,717 @@ impl Document {
     }
 
     pub fn filetype(&self) -> String {
-        self.filetype.clone()
+        self.filetype
+            .map_or(DEFAULT_FILETYPE, |filetype| filetype.name)
+            .to_string()
+    }
+
+    /// one level of indentation.
+    pub fn indent_unit(&self) -> String {
+        self.indent.unit()
+    }
+
+    pub fn indent(&self) -> Indent {
+        self.indent
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// the line break inserted into line `y`, with mixed line endings it's the one the line already has.
+    fn newline(&self, y: usize) -> &'static str {
+        match self.line_ending {
+            LineEnding::Mixed => {
+                LineEnding::of(self.line(y)).map_or("\n", |ending| ending.as_str())
+            }
+            ending => ending.as_str(),
+        }
+    }
+
+    /// convert every line break to `line_ending` as one undo step.
+    ///
+    /// the cursor positions stay the same, the `\r` of a `\r\n` is never a grapheme of its own.
+    pub fn set_line_ending(&mut self, before: ViewState, line_ending: LineEnding) {
+        self.history.begin(before.clone());
+        for y in (0..self.len()).rev() {
+            let line = self.content.line(y);
+            let current = match LineEnding::of(line) {
+                Some(current) if current != line_ending => current,
+                _ => continue,
+            };
+
+            let end = self.content.line_to_char(y) + line.len_chars();
+            let start = end - current.as_str().len();
+            self.remove_range(start, end);
+            self.insert_at(start, line_ending.as_str());
+        }
+        self.history.commit(before);
+        self.line_ending = line_ending;
+    }
+
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width.max(1);
+        self.line_index.get_mut().clear();
+    }
+
+    /// get line by index, `\t` expanded by the tab width.
+    pub fn row_to_line(&self, index: usize) -> Line {
+        if self.binary {
+            return hex::row_line(&self.row_bytes(index));
+        }
+
+        self.line(index).to_line_with_tab(self.tab_width)
+    }
+
+    /// like [`Document::row_to_line`], but a long line is only converted from the grapheme `start`
+    /// for the width of a screen, see [`RopeSliceEx::to_window`].
+    pub fn row_window(&self, index: usize, start: usize) -> Line {
+        let slice = self.line(index);
+        if self.binary || slice.len_chars() <= LONG_LINE {
+            return self.row_to_line(index);
+        }
+
+        let at = self.seek(index, Seek::Grapheme(start));
+        slice
+            .slice(at.offset..)
+            .to_window(self.tab_width, at.x, at.column, WINDOW_WIDTH)
+    }
+
+    /// the display column of the cursor position from the start of its line, `\t` expanded.
+    ///
+    /// past the end of the line it's the width of the line.
+    pub fn column(&self, pos: Position) -> usize {
+        match !self.binary && self.line(pos.y).len_chars() > LONG_LINE {
+            true => self.seek(pos.y, Seek::Grapheme(pos.x)).column,
+            false => self.row_to_line(pos.y).get_offset(pos.x),
+        }
+    }
+
+    /// the grapheme of line `y` that the display column `column` is in, a wide glyph is in both
+    /// of its columns. past the end of the line it's the end of the line.
+    pub fn grapheme_at_column(&self, y: usize, column: usize) -> usize {
+        if !self.binary && self.line(y).len_chars() > LONG_LINE {
+            return self.seek(y, Seek::Column(column)).x;
+        }
+
+        let line = self.row_to_line(y);
+        line.grapheme_at(column).unwrap_or(line.len())
+    }
+
+    /// the grapheme of the long line `y` that `target` points to, it's walked to from the closest
+    /// checkpoint before it, see [`LineIndex`].
+    ///
+    /// the walk stops at the end of the text, the line break is never a grapheme of the line.
+    fn seek(&self, y: usize, target: Seek) -> Checkpoint {
+        let slice = self.line(y);
+        let end = text_len(slice);
+        let mut index = self.line_index.borrow_mut();
+        let mut at = index.last_before(y, |checkpoint| match target {
+            Seek::Grapheme(x) => checkpoint.x <= x,
+            Seek::Char(offset) => checkpoint.offset <= offset,
+            Seek::Column(column) => checkpoint.column <= column,
+        });
+
+        let mut found = Vec::new();
+        for grapheme in RopeGraphemes::new(slice.slice(at.offset..end)) {
+            let str: Cow<str> = grapheme.into();
+            let width = match str.as_ref() {
+                "\t" => self.tab_width - at.column % self.tab_width,
+                str => str.width(),
+            };
+            let reached = match target {
+                Seek::Grapheme(x) => at.x >= x,
+                Seek::Char(offset) => at.offset + grapheme.len_chars() > offset,
+                Seek::Column(column) => at.column + width.max(1) > column,
+            };
+            if reached {
+                break;
+            }
+
+            at = Checkpoint {
+                x: at.x + 1,
+                offset: at.offset + grapheme.len_chars(),
+                column: at.column + width,
+            };
+            if at.x.is_multiple_of(CHECKPOINT_GAP) {
+                found.push(at);
+            }
+        }
+        index.insert(y, found);
+        at
+    }
+
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// the bytes of row `index` of a binary document.
+    pub fn row_bytes(&self, index: usize) -> Vec<u8> {
+        let len = self.content.len_chars();
+        let start = (index * BYTES_PER_ROW).min(len);
+        let end = (start + BYTES_PER_ROW).min(len);
+        self.content
+            .slice(start..end)
+            .chars()
+            .map(|c| c as u8)
+            .collect()
+    }
+
+    /// has the content changed since the last save?
+    pub fn is_modified(&self) -> bool {
+        self.history.revision() != self.saved_revision
+    }
+
+    /// convert the cursor position (grapheme index, line) to a char index in the rope.
+    ///
+    /// the position is clamped to the document, `x` never goes past the line ending.
+    pub fn char_idx(&self, pos: Position) -> usize {
+        let y = pos.y.min(self.len().saturating_sub(1));
+        if !self.binary && self.line(y).len_chars() > LONG_LINE {
+            return self.content.line_to_char(y) + self.seek(y, Seek::Grapheme(pos.x)).offset;
+        }
+
+        let x = self.line_len_at_most(y, pos.x);
+        if self.binary {
+            return (y * BYTES_PER_ROW + x).min(self.content.len_chars());
+        }
+
+        self.content.line_to_char(y) + nth_next_grapheme_boundary(self.content.line(y), 0, x)
+    }
+
+    /// convert a char index in the rope to the cursor position (grapheme index, line).
+    pub fn position(&self, char_idx: usize) -> Position {
+        let char_idx = char_idx.min(self.content.len_chars());
+        if self.binary {
+            return Position {
+                x: char_idx % BYTES_PER_ROW,
+                y: char_idx / BYTES_PER_ROW,
+            };
+        }
+        let y = self.content.char_to_line(char_idx);
+        let line_start = self.content.line_to_char(y);
+        if self.line(y).len_chars() > LONG_LINE {
+            let offset = char_idx - line_start;
+            let at = self.seek(y, Seek::Char(offset));
+            // a char inside a grapheme counts it, like the slice up to the char does
+            return Position {
+                x: at.x + usize::from(at.offset < offset),
+                y,
+            };
+        }
+
+        Position {
+            x: self.content.slice(line_start..char_idx).len_word_boundary(),
+            y,
+        }
+    }
+
+    /// run an edit at every range of the selection as one undo step, `before` is restored when it's undone.
+    ///
+    /// `f` gets the ranges from the last to the first and returns the cursor after its edit,
+    /// returns the selection after the edit with cursors that ran into each other merged.
+    pub fn edit<F>(&mut self, before: ViewState, mut f: F) -> Selection
+    where
+        F: FnMut(&mut Self, Range) -> Position,
+    {
+        self.history.begin(before.clone());
+
+        let ranges = before.selection.ranges();
+        let mut cursors = vec![0usize; ranges.len()];
+        for (i, range) in ranges.iter().enumerate().rev() {
+            let len = self.content.len_chars();
+            let cursor = f(self, *range);
+            let grown = self.content.len_chars() as isize - len as isize;
+
+            // the cursors after this edit moved with it.
+            for cursor in cursors[i + 1..].iter_mut() {
+                *cursor = cursor.saturating_add_signed(grown);
+            }
+            cursors[i] = self.char_idx(cursor);
+        }
+
+        let ranges = cursors
+            .into_iter()
+            .map(|cursor| Range::point(self.position(cursor)))
+            .collect();
+        let selection = Selection::new(ranges, before.selection.primary_index());
+        self.history.commit(ViewState {
+            selection: selection.clone(),
+            offset: before.offset,
+        });
+
+        selection
+    }
+
+    /// the next edit is an undo step of its own, it isn't grouped with the typing before it.
+    pub fn close_group(&mut self) {
+        self.history.close_group();
+    }
+
+    /// revert the last undo step, returns the view from before it.
+    pub fn undo(&mut self) -> Option<ViewState> {
+        let transaction = self.history.undo()?;
+        transaction.revert(&mut self.content);
+        self.line_index.get_mut().clear();
+
+        let (view, converted) = (
+            transaction.before.clone(),
+            converts_line_endings(transaction),
+        );
+        if converted {
+            self.line_ending = line_ending::detect(&self.content);
+        }
+        Some(view)
+    }
+
+    /// apply the last undone step again, returns the view from after it.
+    pub fn redo(&mut self) -> Option<ViewState> {
+        let transaction = self.history.redo()?;
+        transaction.apply(&mut self.content);
+        self.line_index.get_mut().clear();
+
+        let (view, converted) = (
+            transaction.after.clone(),
+            converts_line_endings(transaction),
+        );
+        if converted {
+            self.line_ending = line_ending::detect(&self.content);
+        }
+        Some(view)
+    }
+
+    /// insert text at the cursor position, returns the cursor position after the text.
+    ///
+    /// the line breaks of the text become `\r\n` when the document uses them.
+    pub fn insert(&mut self, pos: Position, text: &str) -> Position {
+        let crlf;
+        let text = match self.line_ending {
+            LineEnding::Crlf if text.contains('\n') => {
+                crlf = text.replace("\r\n", "\n").replace('\n', "\r\n");
+                crlf.as_str()
+            }
+            _ => text,
+        };
+
+        let char_idx = self.char_idx(pos);
+        self.insert_at(char_idx, text);
+
+        self.position(char_idx + text.chars().count())
+    }
+
+    /// split the line at the cursor position, the new line keeps the indentation.
+    ///
+    /// after an opening bracket (or a `:` for some filetypes) the new line is indented one more level,
+    /// a closing bracket right after the cursor moves to a line of its own.
+    pub fn insert_newline(&mut self, pos: Position) -> Position {
+        // the line is never copied, it can be very long.
+        let char_idx = self.char_idx(pos);
+        let before = self
+            .content
+            .slice(self.content.line_to_char(pos.y)..char_idx);
+        let indent: String = before.chars().take_while(|c| is_indent(*c)).collect();
+        let after = self.content.chars_at(char_idx).find(|c| !is_indent(*c));
+        let newline = self.newline(pos.y);
+
+        let opened = before
+            .chars_at(before.len_chars())
+            .reversed()
+            .find(|c| !c.is_whitespace())
+            .zip(self.filetype)
+            .filter(|(c, filetype)| filetype.indents_after(*c));
+        let (opened, filetype) = match opened {
+            Some(opened) => opened,
+            None => return self.insert(pos, &format!("{}{}", newline, indent)),
+        };
+
+        let cursor = self.insert(pos, &format!("{}{}{}", newline, indent, self.indent_unit()));
+        if after.is_some() && after == filetype.closing(opened) {
+            self.insert(cursor, &format!("{}{}", newline, indent));
+        }
+
+        cursor
+    }
+
+    /// indent at the cursor position with the indentation style of the document.
+    ///
+    /// with spaces, as many are inserted as it takes to reach the next indent column.
+    pub fn insert_indent(&mut self, pos: Position) -> Position {
+        match self.indent {
+            Indent::Tabs => self.insert(pos, "\t"),
+            Indent::Spaces(n) => {
+                let column = self.row_to_line(pos.y).get_offset(pos.x);
+                self.insert(pos, &" ".repeat(n - column % n))
+            }
+        }
+    }
+
+    /// type `c` at the cursor position.
+    ///
+    /// a closing bracket typed on a line with nothing but indentation dedents the line one level.
+    pub fn insert_char(&mut self, pos: Position, c: char) -> Position {
+        let closing = self.filetype.is_some_and(|filetype| filetype.is_closing(c));
+        if !closing {
+            return self.insert(pos, c.encode_utf8(&mut [0; 4]));
+        }
+
+        let (start, line_end) = self.line_bounds(pos.y);
+        let before = self.content.slice(start..self.char_idx(pos));
+        let after = self.content.slice(self.char_idx(pos)..line_end);
+        if before.len_chars() == 0 || !before.chars().chain(after.chars()).all(is_indent) {
+            return self.insert(pos, c.encode_utf8(&mut [0; 4]));
+        }
+        let before = before.to_string();
+
+        let unit = self.indent_unit();
+        let dedent = match before.strip_suffix(unit.as_str()) {
+            Some(_) => unit.chars().count(),
+            None if before.ends_with('\t') => 1,
+            None => before
+                .chars()
+                .rev()
+                .take_while(|c| *c == ' ')
+                .count()
+                .min(unit.len()),
+        };
+        let end = self.char_idx(pos);
+        self.remove_range(end - dedent, end);
+
+        let pos = self.position(end - dedent);
+        self.insert(pos, c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// the selected text.
+    pub fn text(&self, range: Range) -> String {
+        let (start, end) = (self.char_idx(range.start()), self.char_idx(range.end()));
+        self.content.slice(start..end).to_string()
+    }
+
+    /// the selected text as it's copied, the bytes of a binary document are copied as hex.
+    pub fn copy_text(&self, range: Range) -> String {
+        let text = self.text(range);
+        match self.binary {
+            true => hex::to_hex(&text.chars().map(|c| c as u8).collect::<Vec<_>>()),
+            false => text,
+        }
+    }
+
+    /// the word under (or right before) the cursor position, selected from its start to its end.
+    pub fn word_at(&self, pos: Position) -> Option<Range> {
+        if self.binary {
+            return None;
+        }
+
+        let char_idx = self.char_idx(pos);
+        let y = self.content.char_to_line(char_idx);
+        let line_start = self.content.line_to_char(y);
+        let line = self.line(y);
+        let is_word = |from, to| {
+            line.slice(from..to)
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_')
+        };
+
+        // only the graphemes of the word are looked at, not the whole line.
+        let (mut start, mut end) = (char_idx - line_start, char_idx - line_start);
+        while end < line.len_chars() {
+            let next = next_grapheme_boundary(line, end);
+            if !is_word(end, next) {
+                break;
+            }
+            end = next;
+        }
+        while start > 0 {
+            let prev = prev_grapheme_boundary(line, start);
+            if !is_word(prev, start) {
+                break;
+            }
+            start = prev;
+        }
+
+        match start < end {
+            true => Some(Range::new(
+                self.position(line_start + start),
+                self.position(line_start + end),
+            )),
+            false => None,
+        }
+    }
+
+    /// where a word motion from `pos` stops: the end of the next word or the start of the previous one.
+    ///
+    /// past the last word it stops at the end of the line, the line break is a stop of its own
+    /// like it is for `←` / `→`. a binary document has no words.
+    pub fn word_motion(&self, pos: Position, kind: WordKind, forward: bool) -> Position {
+        if self.binary {
+            return pos;
+        }
+
+        let char_idx = self.char_idx(pos);
+        let y = self.content.char_to_line(char_idx);
+        let (start, end) = self.line_bounds(y);
+        let found = match forward {
+            true => self
+                .word_boundary(y, char_idx, kind, true)
+                .or_else(|| (char_idx < end).then_some(end))
+                .or_else(|| (y + 1 < self.len()).then(|| self.line_bounds(y + 1).0)),
+            false => self
+                .word_boundary(y, char_idx, kind, false)
+                .or_else(|| (char_idx > start).then_some(start))
+                .or_else(|| y.checked_sub(1).map(|y| self.line_bounds(y).1)),
+        };
+
+        self.position(found.unwrap_or(char_idx))
+    }
+
+    /// the chars from the start of line `y` to its line break.
+    fn line_bounds(&self, y: usize) -> (usize, usize) {
+        let line = self.line(y);
+        let start = self.content.line_to_char(y);
+        let ending = LineEnding::of(line).map_or(0, |ending| ending.as_str().len());
+        (start, start + line.len_chars() - ending)
+    }
+
+    /// the word boundary after (or before) the char `at` of line `y`, see [`word::next_end`].
+    ///
+    /// of a long line only the chars around `at` are looked at.
+    fn word_boundary(&self, y: usize, at: usize, kind: WordKind, forward: bool) -> Option<usize> {
+        let (start, end) = self.line_bounds(y);
+        let (from, to) = (
+            at.saturating_sub(LONG_LINE).max(start),
+            (at + LONG_LINE).min(end),
+        );
+        let text = self.content.slice(from..to).to_string();
+        let at = text
+            .char_indices()
+            .nth(at - from)
+            .map_or(text.len(), |(i, _)| i);
+
+        let found = match forward {
+            true => word::next_end(&text, at, kind),
+            false => word::prev_start(&text, at, kind),
+        }?;
+        Some(from + text[..found].chars().count())
+    }
+
+    /// the next occurrence of `text` after `pos`, wraps around to the start of the document.
+    pub fn find_next(&self, text: &str, pos: Position) -> Option<Range> {
+        if text.is_empty() {
+            return None;
+        }
+
+        let (from, len) = (self.char_idx(pos), text.chars().count());
+        // the matches from the start that end after `from` are found by the first search.
+        let before = (from + len - 1).min(self.content.len_chars());
+        let start = self
+            .content
+            .slice(from..)
+            .find_str(text)
+            .map(|start| start + from)
+            .or_else(|| self.content.slice(..before).find_str(text))?;
+
+        Some(Range::new(
+            self.position(start),
+            self.position(start + text.chars().count()),
+        ))
+    }
+
+    /// remove the selected text, returns the position where it started.
+    pub fn delete_range(&mut self, range: Range) -> Position {
+        let (start, end) = (self.char_idx(range.start()), self.char_idx(range.end()));
+        self.remove_range(start, end);
+
+        self.position(start)
+    }
+
+    /// remove the grapheme before the cursor position (backspace).
+    ///
+    /// at the start of a line the line is joined with the previous one.
+    pub fn delete_backward(&mut self, pos: Position) -> Position {
+        let char_idx = self.char_idx(pos);
+        if char_idx == 0 {
+            return self.position(char_idx);
+        }
+
+        let start = match self.binary {
+            true => char_idx - 1,
+            false => prev_grapheme_boundary(self.content.slice(..), char_idx),
+        };
+        self.remove_range(start, char_idx);
+
+        self.position(start)
+    }
+
+    /// remove the grapheme under the cursor position (delete).
+    ///
+    /// at the end of a line the next line is joined to it.
+    pub fn delete_forward(&mut self, pos: Position) -> Position {
+        let char_idx = self.char_idx(pos);
+        let end = match self.binary {
+            true => (char_idx + 1).min(self.content.len_chars()),
+            false => next_grapheme_boundary(self.content.slice(..), char_idx),
+        };
+        if end > char_idx {
+            self.remove_range(char_idx, end);
+        }
+
+        self.position(char_idx)
+    }
+
+    /// remove from the cursor position to where a word motion stops, see [`Document::word_motion`].
+    pub fn delete_word(&mut self, pos: Position, kind: WordKind, forward: bool) -> Position {
+        let to = self.word_motion(pos, kind, forward);
+        self.delete_range(Range::new(pos, to))
+    }
+
+    /// insert bytes into a binary document, returns the position after them.
+    pub fn insert_bytes(&mut self, pos: Position, bytes: &[u8]) -> Position {
+        let char_idx = self.char_idx(pos);
+        let text: String = bytes.iter().map(|byte| *byte as char).collect();
+        self.insert_at(char_idx, &text);
+
+        self.position(char_idx + bytes.len())
+    }
+
+    /// overwrite the high (or `low`) nibble of the byte at the position of a binary document with `digit`,
+    /// after the position a new byte is appended.
+    ///
+    /// returns the position of the next nibble, after the low one it's the next byte.
+    pub fn overwrite_nibble(&mut self, pos: Position, low: bool, digit: u8) -> Position {
+        let char_idx = self.char_idx(pos);
+        let byte = self.content.get_char(char_idx).map_or(0, |c| c as u8);
+        let byte = match low {
+            true => byte & 0xf0 | digit & 0x0f,
+            false => byte & 0x0f | digit << 4,
+        };
+
+        self.remove_range(char_idx, (char_idx + 1).min(self.content.len_chars()));
+        self.insert_at(char_idx, &(byte as char).to_string());
+
+        self.position(char_idx + low as usize)
+    }
+
+    /// every insertion into the content goes through here.
+    fn insert_at(&mut self, char_idx: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.apply(Change::insert(char_idx, text));
+    }
+
+    /// every removal from the content goes through here.
+    fn remove_range(&mut self, start: usize, end: usize) {
+        if start >= end {
+            return;
+        }
+
+        let removed = self.content.slice(start..end).to_string();
+        self.apply(Change::remove(start, &removed));
+    }
+
+    fn apply(&mut self, change: Change) {
+        let breaks = [&change.removed, &change.inserted]
+            .iter()
+            .any(|text| text.contains(is_line_break));
+        let line_index = self.line_index.get_mut();
+        match breaks {
+            true => line_index.clear(),
+            false => {
+                let y = self.content.char_to_line(change.at);
+                line_index.edited(y, change.at - self.content.line_to_char(y));
+            }
+        }
+
+        change.apply(&mut self.content);
+        self.history.record(change);
+    }
+}
+
+/// where [`Document::seek`] walks to on a long line.
+#[derive(Copy, Clone)]
+enum Seek {
+    /// the grapheme index
+    Grapheme(usize),
+    /// the grapheme the char offset from the line start is in
+    Char(usize),
+    /// the grapheme the display column is in
+    Column(usize),
+}
+
+/// the chars of a line before its line break, the line break is the last grapheme.
+fn text_len(line: RopeSlice) -> usize {
+    match line.len_lines() > 1 {
+        true => prev_grapheme_boundary(line, line.len_chars()),
+        false => line.len_chars(),
+    }
+}
+
+/// a char that ends a line, see [`ropey::Rope::len_lines`].
+fn is_line_break(c: char) -> bool {
+    matches!(
+        c,
+        '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}'
+    )
+}
+
+/// does undoing / redoing the transaction change the line endings, e.g. it's a conversion?
+fn converts_line_endings(transaction: &Transaction) -> bool {
+    transaction
+        .changes
+        .iter()
+        .any(|change| change.removed.contains('\r') || change.inserted.contains('\r'))
+}
+
+/// a char of the indentation at the start of a line.
+fn is_indent(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// the temp file used by [`Document::save`], a hidden file in the same directory as `path`,
+/// so the final rename never crosses a filesystem.
+fn tmp_path(path: &Path) -> PathBuf {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    path.with_file_name(format!(".{}.{}.raw-tmp", filename, std::process::id()))
+}
+
+impl Default for Document {
+    fn default() -> Self {
+        Self::from(Default::default(), DEFAULT_FILENAME)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::render::document::Document;
+    use crate::app::AppCtx;
+    use crate::history::ViewState;
+    use crate::indent::Indent;
+    use crate::line_ending::LineEnding;
+    use crate::line_index::CHECKPOINT_GAP;
+    use crate::render::document::{Document, SELECTION_BG};
+    use crate::render::Render;
+    use crate::screen::Position;
+    use crate::selection::{Range, Selection};
+    use ropey::Rope;
+    use tui::buffer::Buffer;
+    use tui::layout::Rect;
+
+    /// the cursor position `x`, `y`.
+    fn pos(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
 
     #[test]
     fn test_line_len() {
@@ -146,4 +1499,391 @@ mod tests {
         println!("{}", doc.line(0));
         println!("{}", doc.line_len(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut doc = Document::from(Rope::from("1好👩‍🔬\nabc"), "test");
+
+        let pos = doc.insert(Position { x: 2, y: 0 }, "x");
+        assert_eq!(doc.line(0).to_string(), "1好x👩‍🔬\n");
+        assert_eq!(pos, Position { x: 3, y: 0 });
+
+        let pos = doc.delete_forward(pos);
+        assert_eq!(doc.line(0).to_string(), "1好x\n");
+        assert_eq!(pos, Position { x: 3, y: 0 });
+
+        let pos = doc.delete_backward(Position { x: 0, y: 1 });
+        assert_eq!(doc.content.to_string(), "1好xabc");
+        assert_eq!(pos, Position { x: 3, y: 0 });
+
+        let pos = doc.insert_newline(Position { x: 2, y: 0 });
+        assert_eq!(doc.content.to_string(), "1好\nxabc");
+        assert_eq!(pos, Position { x: 0, y: 1 });
+
+        let pos = doc.delete_backward(Position { x: 0, y: 0 });
+        assert_eq!(pos, Position { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn test_undo_restores_view() {
+        let mut doc = Document::from(Rope::from("abc"), "test");
+        let before = ViewState {
+            selection: Selection::point(Position { x: 3, y: 0 }),
+            offset: Position { x: 1, y: 0 },
+        };
+
+        let selection = doc.edit(before.clone(), |doc, range| doc.insert_newline(range.head));
+        assert_eq!(selection.cursor(), Position { x: 0, y: 1 });
+        assert!(doc.is_modified());
+
+        assert_eq!(doc.undo(), Some(before));
+        assert_eq!(doc.content.to_string(), "abc");
+        assert!(!doc.is_modified());
+
+        assert_eq!(doc.redo().map(|view| view.selection), Some(selection));
+        assert_eq!(doc.content.to_string(), "abc\n");
+        assert!(doc.undo().is_some());
+        assert!(doc.undo().is_none());
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut doc = Document::from(Rope::from("1好a\nabc"), "test");
+        let range = Range::new(Position { x: 1, y: 1 }, Position { x: 1, y: 0 });
+        assert_eq!(doc.text(range), "好a\na");
+
+        let ctx = AppCtx {
+            current_line: Default::default(),
+            offset: Default::default(),
+            cursor: range.head,
+            selection: Selection::single(range),
+            screen_size: (10, 3),
+            doc_size: (0, 0),
+            bottom_height: 0,
+        };
+        let area = Rect::new(0, 0, 10, 3);
+        let mut buf = Buffer::empty(area);
+        doc.render(ctx, &mut buf, area);
+        let selected: Vec<bool> = (0..6).map(|x| buf.get(x, 0).bg == SELECTION_BG).collect();
+        assert_eq!(selected, [false, true, true, true, true, false]);
+        assert!(buf.get(0, 1).bg == SELECTION_BG);
+        assert!(buf.get(1, 1).bg != SELECTION_BG);
+
+        let cursor = doc.delete_range(range);
+        assert_eq!(doc.content.to_string(), "1bc");
+        assert_eq!(cursor, Position { x: 1, y: 0 });
+    }
+
+    #[test]
+    fn test_multiple_cursors() {
+        let mut doc = Document::from(Rope::from("ab\nab\nab"), "test");
+        let mut selection = Selection::point(pos(0, 0));
+        selection.push(Range::point(pos(0, 1)));
+        selection.push(Range::new(pos(0, 2), pos(1, 2)));
+        let before = ViewState {
+            selection,
+            offset: Position::default(),
+        };
+
+        let selection = doc.edit(before, |doc, range| {
+            let cursor = doc.delete_range(range);
+            doc.insert(cursor, "xy")
+        });
+        assert_eq!(doc.content.to_string(), "xyab\nxyab\nxyb");
+        assert_eq!(selection.len(), 3);
+        assert_eq!(selection.cursor(), pos(2, 2));
+        assert_eq!(selection.ranges()[1], Range::point(pos(2, 1)));
+
+        // cursors that run into each other merge.
+        let mut selection = Selection::point(pos(3, 0));
+        selection.push(Range::point(pos(2, 0)));
+        let before = ViewState {
+            selection,
+            offset: Position::default(),
+        };
+        let selection = doc.edit(before, |doc, range| doc.delete_backward(range.head));
+        assert_eq!(doc.content.to_string(), "xb\nxyab\nxyb");
+        assert_eq!(selection, Selection::point(pos(1, 0)));
+        assert!(doc.undo().is_some());
+        assert_eq!(doc.content.to_string(), "xyab\nxyab\nxyb");
+    }
+
+    #[test]
+    fn test_find() {
+        let doc = Document::from(Rope::from("好foo bar\nfoo_1 foo"), "test");
+        assert_eq!(
+            doc.word_at(pos(2, 0)),
+            Some(Range::new(pos(0, 0), pos(4, 0)))
+        );
+        assert_eq!(
+            doc.word_at(pos(4, 0)),
+            Some(Range::new(pos(0, 0), pos(4, 0)))
+        );
+        assert_eq!(
+            doc.word_at(pos(5, 1)),
+            Some(Range::new(pos(0, 1), pos(5, 1)))
+        );
+        assert_eq!(
+            doc.word_at(pos(5, 0)),
+            Some(Range::new(pos(5, 0), pos(8, 0)))
+        );
+
+        let found = doc.find_next("foo", pos(4, 0));
+        assert_eq!(found, Some(Range::new(pos(0, 1), pos(3, 1))));
+        let found = doc.find_next("foo", pos(7, 1));
+        assert_eq!(found, Some(Range::new(pos(1, 0), pos(4, 0))));
+        assert_eq!(doc.find_next("baz", pos(0, 0)), None);
+    }
+
+    #[test]
+    fn test_find_long() {
+        // the rope is split into many chunks, some matches go over two of them.
+        let text = "好foo bar ".repeat(5_000);
+        let doc = Document::from(Rope::from(text.as_str()), "test");
+        let chars: Vec<char> = text.chars().collect();
+        for from in (0..chars.len()).step_by(997) {
+            let found = doc.find_next("o bar 好f", pos(from, 0)).unwrap();
+            let expected = (from..chars.len())
+                .chain(0..from)
+                .find(|at| chars[*at..].starts_with(&"o bar 好f".chars().collect::<Vec<_>>()))
+                .unwrap();
+            assert_eq!(found, Range::new(pos(expected, 0), pos(expected + 8, 0)));
+        }
+        assert_eq!(doc.find_next("bar 好 ", pos(0, 0)), None);
+
+        // the word is found around the cursor, not in a copy of the whole line.
+        let x = chars.len() - 4;
+        assert_eq!(
+            doc.word_at(pos(x, 0)),
+            Some(Range::new(pos(x, 0), pos(x + 3, 0)))
+        );
+        assert_eq!(
+            doc.word_at(pos(x - 3, 0)),
+            Some(Range::new(pos(x - 5, 0), pos(x - 1, 0)))
+        );
+        assert_eq!(
+            doc.word_at(pos(x - 1, 0)),
+            Some(Range::new(pos(x - 5, 0), pos(x - 1, 0)))
+        );
+    }
+
+    #[test]
+    fn test_auto_indent() {
+        let mut doc = Document::from(Rope::from("    if a {}"), "test.rs");
+        let cursor = doc.insert_newline(pos(10, 0));
+        assert_eq!(doc.content.to_string(), "    if a {\n        \n    }");
+        assert_eq!(cursor, pos(8, 1));
+
+        let cursor = doc.insert_newline(cursor);
+        assert_eq!(cursor, pos(8, 2));
+        let cursor = doc.insert_char(cursor, '}');
+        assert_eq!(doc.line(2).to_string(), "    }\n");
+        assert_eq!(cursor, pos(5, 2));
+
+        // the rest of the line isn't blank, it stays as it is.
+        let mut doc = Document::from(Rope::from("        foo"), "test.rs");
+        assert_eq!(doc.insert_char(pos(4, 0), '}'), pos(5, 0));
+        assert_eq!(doc.content.to_string(), "    }    foo");
+
+        // plain text only keeps the indentation.
+        let mut doc = Document::from(Rope::from("  a:"), "test");
+        assert_eq!(doc.insert_newline(pos(4, 0)), pos(2, 1));
+        assert_eq!(doc.insert_char(pos(2, 1), '}'), pos(3, 1));
+        assert_eq!(doc.content.to_string(), "  a:\n  }");
+
+        let mut doc = Document::from(Rope::from("def a():"), "test.py");
+        assert_eq!(doc.insert_newline(pos(8, 0)), pos(4, 1));
+    }
+
+    #[test]
+    fn test_indent() {
+        let mut doc = Document::from(Rope::from("a"), "test");
+        assert_eq!(doc.indent(), Indent::Spaces(4));
+        assert_eq!(doc.insert_indent(pos(1, 0)), pos(4, 0));
+        assert_eq!(doc.content.to_string(), "a   ");
+
+        let mut doc = Document::from(Rope::from("all:\n"), "Makefile");
+        assert_eq!(doc.indent(), Indent::Tabs);
+        let cursor = doc.insert_newline(pos(4, 0));
+        assert_eq!(doc.content.to_string(), "all:\n\t\n");
+
+        doc.set_tab_width(8);
+        assert_eq!(doc.row_to_line(1).get_offset(cursor.x), 8);
+    }
+
+    #[test]
+    fn test_line_ending() {
+        let mut doc = Document::from(Rope::from("ab\r\ncd\r\n"), "test");
+        assert_eq!(doc.line_ending(), LineEnding::Crlf);
+        assert_eq!(doc.line_len(0), 2);
+        assert_eq!(doc.position(doc.char_idx(pos(3, 0))), pos(2, 0));
+
+        let cursor = doc.insert_newline(pos(1, 0));
+        assert_eq!(cursor, pos(0, 1));
+        assert_eq!(doc.content.to_string(), "a\r\nb\r\ncd\r\n");
+        assert_eq!(doc.delete_backward(cursor), pos(1, 0));
+        assert_eq!(doc.insert(pos(2, 1), "x\ny"), pos(1, 2));
+        assert_eq!(doc.content.to_string(), "ab\r\ncdx\r\ny\r\n");
+
+        doc.set_line_ending(ViewState::default(), LineEnding::Lf);
+        assert_eq!(doc.content.to_string(), "ab\ncdx\ny\n");
+        assert_eq!(doc.line_ending(), LineEnding::Lf);
+        assert!(doc.undo().is_some());
+        assert_eq!(doc.line_ending(), LineEnding::Crlf);
+
+        let mut doc = Document::from(Rope::from("a\r\nb\n"), "test");
+        assert_eq!(doc.line_ending(), LineEnding::Mixed);
+        doc.insert_newline(pos(1, 0));
+        doc.insert_newline(pos(1, 2));
+        assert_eq!(doc.content.to_string(), "a\r\n\r\nb\n\n");
+    }
+
+    #[test]
+    fn test_long_line() {
+        let text = format!("{}好\n{{", "a".repeat(super::LONG_LINE));
+        let mut doc = Document::from(Rope::from(text), "test.rs");
+        assert_eq!(doc.line_len(0), super::LONG_LINE + 1);
+        assert_eq!(doc.line_len_at_most(0, 5), 5);
+        assert_eq!(doc.char_idx(pos(usize::MAX, 0)), super::LONG_LINE + 1);
+        assert_eq!(
+            doc.position(super::LONG_LINE + 1),
+            pos(super::LONG_LINE + 1, 0)
+        );
+
+        let line = doc.row_window(0, super::LONG_LINE - 1);
+        assert_eq!(line.start, super::LONG_LINE - 1);
+        assert_eq!(line.str_list, ["a", "好", "\n"]);
+        assert_eq!(line.get_offset(super::LONG_LINE + 1), 3);
+        assert_eq!(doc.row_window(1, super::LONG_LINE).start, 0);
+
+        let cursor = doc.insert_newline(pos(1, 1));
+        assert_eq!(cursor, pos(4, 2));
+        assert_eq!(
+            doc.insert_char(pos(super::LONG_LINE, 0), ')'),
+            pos(super::LONG_LINE + 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_far_right() {
+        let len = 2_000_000;
+        let text = format!("{}\t好b\nc", "a".repeat(len));
+        let doc = Document::from(Rope::from(text), "test");
+        assert_eq!(doc.line_len(0), len + 3);
+
+        // every step starts from a checkpoint near the cursor, not from the line start.
+        for x in (len - 1000..len + 3).rev() {
+            let near = doc.line_index.borrow().last_before(0, |c| c.x <= x);
+            assert!(x - near.x < CHECKPOINT_GAP);
+            assert_eq!((near.offset, near.column), (near.x, near.x));
+
+            assert_eq!(doc.char_idx(pos(x, 0)), x);
+            assert_eq!(doc.position(x), pos(x, 0));
+            assert_eq!(doc.line_len_at_most(0, x + 1), x + 1);
+            assert_eq!(doc.row_window(0, x).start, x);
+        }
+
+        let line = doc.row_window(0, len);
+        assert_eq!(line.str_list, ["\t", "好", "b", "\n"]);
+        // the `\t` is aligned to the columns of the whole line
+        assert_eq!(line.offset_mapping, [0, 4, 6, 7]);
+        assert_eq!(doc.char_idx(pos(usize::MAX, 0)), len + 3);
+        assert_eq!(doc.position(len + 4), pos(0, 1));
+    }
+
+    #[test]
+    fn test_column() {
+        let short = "a\t好b\t";
+        let long = short.repeat(super::LONG_LINE);
+        let doc = Document::from(Rope::from(format!("{short}\n{long}")), "test");
+
+        assert_eq!(doc.column(pos(2, 0)), 4);
+        assert_eq!(doc.column(pos(4, 0)), 7);
+        assert_eq!(doc.column(pos(9, 0)), 8);
+        assert_eq!(doc.grapheme_at_column(0, 2), 1);
+        assert_eq!(doc.grapheme_at_column(0, 5), 2);
+        assert_eq!(doc.grapheme_at_column(0, 7), 4);
+        assert_eq!(doc.grapheme_at_column(0, 8), 5);
+
+        // a long line counts from its start like a short one, every repeat is 8 columns wide.
+        let repeats = super::LONG_LINE - 1;
+        assert_eq!(doc.column(pos(repeats * 5 + 2, 1)), repeats * 8 + 4);
+        assert_eq!(doc.column(pos(usize::MAX, 1)), super::LONG_LINE * 8);
+        assert_eq!(doc.grapheme_at_column(1, repeats * 8 + 5), repeats * 5 + 2);
+        assert_eq!(doc.grapheme_at_column(1, repeats * 8 + 2), repeats * 5 + 1);
+        assert_eq!(doc.grapheme_at_column(1, usize::MAX), super::LONG_LINE * 5);
+    }
+
+    #[test]
+    fn test_long_line_edit() {
+        let text = format!("{}\n{}", "ab".repeat(super::LONG_LINE), "c".repeat(100));
+        let mut doc = Document::from(Rope::from(text), "test");
+        let len = doc.line_len(0);
+
+        doc.insert(pos(len - 300, 0), "好\t");
+        doc.delete_backward(pos(len - 600, 0));
+        doc.insert(pos(len - 400, 0), "x\ny");
+        doc.undo();
+        doc.insert(pos(0, 1), "d\n");
+        doc.insert(pos(5, 2), "👩‍🔬");
+        let fresh = Document::from(doc.content.clone(), "test");
+        for y in 0..doc.len() {
+            assert_eq!(doc.line_len(y), fresh.line_len(y));
+            for x in (0..doc.line_len(y)).step_by(97) {
+                assert_eq!(doc.char_idx(pos(x, y)), fresh.char_idx(pos(x, y)));
+                assert_eq!(
+                    doc.row_window(y, x).str_list,
+                    fresh.row_window(y, x).str_list
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary() {
+        let content: String = (0..=20u8).map(|byte| byte as char).collect();
+        let doc = Document::binary(Rope::from(content), "test");
+        assert!(doc.is_binary());
+        assert_eq!(doc.len(), 2);
+        assert_eq!(doc.line_len(0), 15);
+        assert_eq!(doc.line_len(1), 5);
+        assert_eq!(doc.row_bytes(1), [16, 17, 18, 19, 20]);
+        assert_eq!(doc.char_idx(pos(9, 1)), 21);
+        assert_eq!(doc.position(17), pos(1, 1));
+        assert_eq!(doc.position(21), pos(5, 1));
+        assert_eq!(doc.row_to_line(0).get_offset(1), 13);
+        assert_eq!(doc.encoding_name(), "binary");
+        assert_eq!(doc.word_at(pos(0, 0)), None);
+
+        let full: String = (0..16u8).map(|byte| byte as char).collect();
+        let doc = Document::binary(Rope::from(full), "test");
+        assert_eq!(doc.len(), 2);
+        assert_eq!(doc.position(16), pos(0, 1));
+    }
+
+    #[test]
+    fn test_hex_edit() {
+        let bytes = |doc: &Document| doc.content.chars().map(|c| c as u8).collect::<Vec<_>>();
+        let mut doc = Document::binary(Rope::from("\r\n\u{ff}"), "test");
+
+        assert_eq!(doc.overwrite_nibble(pos(0, 0), false, 0xa), pos(0, 0));
+        assert_eq!(doc.overwrite_nibble(pos(0, 0), true, 0xb), pos(1, 0));
+        assert_eq!(bytes(&doc), [0xab, 0x0a, 0xff]);
+
+        assert_eq!(doc.overwrite_nibble(pos(3, 0), false, 1), pos(3, 0));
+        assert_eq!(bytes(&doc), [0xab, 0x0a, 0xff, 0x10]);
+
+        assert_eq!(doc.insert_bytes(pos(1, 0), &[0, 0]), pos(3, 0));
+        assert_eq!(doc.delete_backward(pos(3, 0)), pos(2, 0));
+        assert_eq!(doc.delete_forward(pos(0, 0)), pos(0, 0));
+        assert_eq!(bytes(&doc), [0, 0x0a, 0xff, 0x10]);
+
+        assert_eq!(
+            doc.find_next("\n\u{ff}", pos(0, 0)),
+            Some(Range::new(pos(1, 0), pos(3, 0)))
+        );
+        assert_eq!(doc.copy_text(Range::new(pos(1, 0), pos(3, 0))), "0a ff");
+        assert!(doc.undo().is_some());
+        assert_eq!(bytes(&doc), [0xab, 0, 0x0a, 0xff, 0x10]);
+    }
+}
@@ -0,0 +1,218 @@
+use tui::buffer::Buffer;
+use tui::layout::Rect;
+use tui::style::{Modifier, Style};
+
+use crate::app::AppCtx;
+use crate::extension::rope::Line;
+use crate::render::document::{Document, SELECTION_BG};
+use crate::render::Render;
+
+/// how many bytes are shown in a row.
+pub const BYTES_PER_ROW: usize = 16;
+
+/// the offset column, `00000000: `.
+const OFFSET_WIDTH: usize = 10;
+
+/// the ascii gutter starts after the hex bytes and a space.
+const ASCII_COLUMN: usize = OFFSET_WIDTH + BYTES_PER_ROW * 3;
+
+/// a hex dump of a binary document: the offset column, the hex bytes and an ascii gutter.
+///
+/// every byte of a binary document is a char of its content, see [`Document::binary`].
+pub struct HexView<'a> {
+    doc: &'a Document,
+}
+
+impl<'a> HexView<'a> {
+    pub fn new(doc: &'a Document) -> Self {
+        Self { doc }
+    }
+}
+
+impl Render for HexView<'_> {
+    fn name(&self) -> String {
+        self.doc.name()
+    }
+
+    fn render(&mut self, ctx: AppCtx, buf: &mut Buffer, area: Rect) {
+        let ranges: Vec<(usize, usize)> = ctx
+            .selection
+            .ranges()
+            .iter()
+            .map(|range| {
+                (
+                    self.doc.char_idx(range.start()),
+                    self.doc.char_idx(range.end()),
+                )
+            })
+            .collect();
+        let cursor = self.doc.char_idx(ctx.cursor);
+
+        for y in 0..area.height as usize {
+            if ctx.offset.y + y >= self.doc.len() {
+                return;
+            }
+            let row = self.doc.row_bytes(ctx.offset.y + y);
+
+            let line = row_line(&row);
+            let origin = line.origin(ctx.offset.x);
+            let offset = (ctx.offset.y + y) * BYTES_PER_ROW;
+            let text = format!(
+                "{:08x}: {:<width$} {}",
+                offset,
+                line.str_list.join(" "),
+                row.iter().map(|byte| ascii(*byte)).collect::<String>(),
+                width = BYTES_PER_ROW * 3 - 1
+            );
+            let text: String = text.chars().skip(origin).collect();
+            buf.set_stringn(
+                area.x,
+                area.y + y as u16,
+                text,
+                area.width as usize,
+                Style::default(),
+            );
+
+            // the cursor can be after the last byte, where a byte is appended.
+            for x in 0..(row.len() + 1).min(BYTES_PER_ROW) {
+                let pos = offset + x;
+                if ranges
+                    .iter()
+                    .any(|(start, end)| *start <= pos && pos < *end)
+                {
+                    let style = Style::default().bg(SELECTION_BG);
+                    set_style(buf, area, line.get_offset(x), 2, origin, y, style);
+                    set_style(buf, area, ASCII_COLUMN + x, 1, origin, y, style);
+                }
+                // the terminal cursor is on the hex digits, mark the byte in the gutter too.
+                if pos == cursor {
+                    let style = Style::default().add_modifier(Modifier::REVERSED);
+                    set_style(buf, area, ASCII_COLUMN + x, 1, origin, y, style);
+                }
+            }
+        }
+    }
+}
+
+/// style `width` cells from the `column` of a row, `origin` columns are scrolled out of the view.
+fn set_style(
+    buf: &mut Buffer,
+    area: Rect,
+    column: usize,
+    width: usize,
+    origin: usize,
+    y: usize,
+    style: Style,
+) {
+    if column < origin || column - origin + width > area.width as usize {
+        return;
+    }
+
+    buf.set_style(
+        Rect {
+            x: area.x + (column - origin) as u16,
+            y: area.y + y as u16,
+            width: width as u16,
+            height: 1,
+        },
+        style,
+    );
+}
+
+/// the hex digits of a row as a [`Line`], a byte is a grapheme at the column of its digits,
+/// so the cursor and the scrolling work like they do for text.
+pub fn row_line(row: &[u8]) -> Line {
+    Line {
+        offset_mapping: (0..row.len()).map(|x| OFFSET_WIDTH + x * 3).collect(),
+        str_list: row.iter().map(|byte| format!("{:02x}", byte)).collect(),
+        width: OFFSET_WIDTH + row.len() * 3,
+        start: 0,
+    }
+}
+
+/// bytes as hex, e.g. `de ad be ef`.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// parse hex bytes, whitespace between them is ignored, `None` unless every byte has two digits.
+pub fn parse(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|digit| digit as u8))
+        .collect::<Option<_>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
+
+/// a byte in the ascii gutter, `.` when it isn't printable.
+fn ascii(byte: u8) -> char {
+    match byte {
+        0x20..=0x7e => byte as char,
+        _ => '.',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+    use tui::buffer::Buffer;
+    use tui::layout::Rect;
+    use tui::style::Modifier;
+
+    use crate::app::AppCtx;
+    use crate::render::document::Document;
+    use crate::render::hex::{parse, row_line, to_hex, HexView};
+    use crate::render::Render;
+    use crate::screen::Position;
+
+    #[test]
+    fn test_render() {
+        let doc = Document::binary(Rope::from("ab\u{0}\u{ff}"), "test");
+        let area = Rect::new(0, 0, 80, 2);
+        let mut buf = Buffer::empty(area);
+        let ctx = AppCtx {
+            current_line: Default::default(),
+            offset: Default::default(),
+            cursor: Position { x: 1, y: 0 },
+            selection: Default::default(),
+            screen_size: (80, 2),
+            doc_size: (0, 0),
+            bottom_height: 0,
+        };
+        HexView::new(&doc).render(ctx, &mut buf, area);
+
+        let row: String = (0..80).map(|x| buf.get(x, 0).symbol.clone()).collect();
+        assert_eq!(
+            row.trim_end(),
+            format!("00000000: 61 62 00 ff{}ab..", " ".repeat(37))
+        );
+        assert!(buf.get(59, 0).modifier.contains(Modifier::REVERSED));
+
+        let line = row_line(&[0x61, 0x62]);
+        assert_eq!(line.get_offset(1), 13);
+        assert_eq!(line.get_offset(2), 16);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("de ad BEef"), Some(vec![0xde, 0xad, 0xbe, 0xef]));
+        assert_eq!(parse(""), Some(vec![]));
+        assert_eq!(parse("abc"), None);
+        assert_eq!(parse("zz"), None);
+        assert_eq!(to_hex(&[0, 0x0a, 0xff]), "00 0a ff");
+    }
+}
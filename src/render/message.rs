@@ -18,12 +18,12 @@ impl Render for MessageBar {
         "message".to_string()
     }
 
-    fn render(&mut self, ctx: AppCtx, buf: &mut Buffer, area: Rect) {
+    fn render(&mut self, _ctx: AppCtx, buf: &mut Buffer, area: Rect) {
         if !self.should_render() {
             return;
         }
 
-        Paragraph::new(format!("text message: {}", self.content))
+        Paragraph::new(self.content.as_str())
             .alignment(Alignment::Center)
             .style(Style::default().bg(Color::Red))
             .render(area, buf);
@@ -39,6 +39,11 @@ impl MessageBar {
         }
     }
 
+    /// a message that stays until it is replaced.
+    pub fn sticky(content: String) -> Self {
+        Self::new(content, Duration::MAX)
+    }
+
     /// should render message bar
     pub fn should_render(&self) -> bool {
         if self.content.is_empty() {
@@ -51,7 +56,7 @@ impl MessageBar {
 
 impl Default for MessageBar {
     fn default() -> Self {
-        MessageBar::from("11111111")
+        MessageBar::from("")
     }
 }
 
@@ -70,4 +75,4 @@ impl From<String> for MessageBar {
             delay: Duration::new(5, 0),
         }
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,6 @@
 use crate::app::AppCtx;
+use crate::line_ending::LineEnding;
+use crate::render::document::Document;
 use crate::render::Render;
 use crate::{DEFAULT_FILENAME, DEFAULT_FILETYPE};
 use tui::buffer::Buffer;
@@ -10,6 +12,13 @@ use tui::widgets::{Paragraph, Widget};
 pub struct StatusLine {
     filename: String,
     filetype: String,
+    modified: bool,
+    line_ending: String,
+    encoding: String,
+    /// the file is still being read
+    loading: bool,
+    /// how much of the file is loaded, see [`Document::load_progress`]
+    progress: Option<u8>,
     bg: Style,
     fg: Style,
 }
@@ -42,6 +51,11 @@ impl Default for StatusLine {
         Self {
             filename: DEFAULT_FILENAME.to_string(),
             filetype: DEFAULT_FILETYPE.to_string(),
+            modified: false,
+            line_ending: LineEnding::default().to_string(),
+            encoding: "UTF-8".to_string(),
+            loading: false,
+            progress: None,
             bg: Style::default().bg(Color::Rgb(124, 252, 200)), // .bg(Color::Rgb(201, 123, 193)),
             fg: Style::default()
                 .fg(Color::Rgb(30, 30, 46))
@@ -51,9 +65,14 @@ impl Default for StatusLine {
 }
 
 impl StatusLine {
-    pub fn refresh(&mut self, name: String, file_type: String) {
-        self.filename = name;
-        self.filetype = file_type;
+    pub fn refresh(&mut self, doc: &Document) {
+        self.filename = doc.name();
+        self.filetype = doc.filetype();
+        self.modified = doc.is_modified();
+        self.line_ending = doc.line_ending().to_string();
+        self.encoding = doc.encoding_name();
+        self.loading = !doc.is_loaded();
+        self.progress = doc.load_progress();
     }
 
     fn render_bg(&self, buf: &mut Buffer, area: Rect) {
@@ -61,7 +80,13 @@ impl StatusLine {
     }
 
     fn render_filename(&self, buf: &mut Buffer, area: Rect) {
-        Paragraph::new(format!(" 📝 {}", self.filename))
+        let marker = if self.modified { " [+]" } else { "" };
+        let progress = match (self.loading, self.progress) {
+            (true, Some(progress)) => format!(" loading {}%", progress),
+            (true, None) => " loading".to_string(),
+            (false, _) => String::new(),
+        };
+        Paragraph::new(format!(" 📝 {}{}{}", self.filename, marker, progress))
             .style(self.fg)
             .alignment(Alignment::Left)
             .render(area, buf);
@@ -103,9 +128,12 @@ impl StatusLine {
     }
 
     fn render_filetype(&self, buf: &mut Buffer, area: Rect) {
-        Paragraph::new(self.filetype.as_str())
-            .style(self.fg)
-            .alignment(Alignment::Right)
-            .render(area, buf);
+        Paragraph::new(format!(
+            "{} {} {} ",
+            self.encoding, self.line_ending, self.filetype
+        ))
+        .style(self.fg)
+        .alignment(Alignment::Right)
+        .render(area, buf);
     }
-}
\ No newline at end of file
+}
@@ -1,3 +1,6 @@
+use std::io::{self, IsTerminal};
+use std::time::{Duration, Instant};
+
 use ropey::RopeSlice;
 
 use tui::buffer::Buffer;
@@ -5,11 +8,20 @@ use tui::layout::Rect;
 
 use crate::app::AppCtx;
 use crate::extension::rect::RectEx;
-use crate::extension::rope::{Line, RopeSliceEx};
+use crate::extension::rope::Line;
 use crate::render::document::Document;
+use crate::render::hex::HexView;
 use crate::render::message::MessageBar;
 use crate::render::status_line::StatusLine;
 use crate::render::Render;
+use crate::screen::Position;
+use crate::{DEFAULT_FILENAME, DEFAULT_TAB_WIDTH};
+
+/// how long a tick may spend appending what the loaders have read.
+const LOAD_BUDGET: Duration = Duration::from_millis(50);
+
+/// how often the unsaved changes are written to the swap files.
+const SWAP_INTERVAL: Duration = Duration::from_secs(2);
 
 pub struct DocumentSwitcher {
     documents: Vec<Document>,
@@ -17,6 +29,10 @@ pub struct DocumentSwitcher {
     empty: bool,
     status_line: StatusLine,
     message_bar: MessageBar,
+    /// the tab width of every document
+    tab_width: usize,
+    /// when the swap files were last written
+    swapped_at: Instant,
 }
 
 impl Render for DocumentSwitcher {
@@ -27,14 +43,16 @@ impl Render for DocumentSwitcher {
     fn render(&mut self, ctx: AppCtx, buf: &mut Buffer, area: Rect) {
         let should_render_message_bar = self.message_bar.should_render();
 
-        self.current_mut().unwrap().render(
-            ctx.clone(),
-            buf,
-            area.to_document(should_render_message_bar),
-        );
+        let doc = self.current_mut().unwrap();
+        let area_doc = area.to_document(should_render_message_bar);
+        if doc.is_binary() {
+            HexView::new(doc).render(ctx.clone(), buf, area_doc);
+        } else {
+            doc.render(ctx.clone(), buf, area_doc);
+        }
 
-        let current = self.current().unwrap();
-        self.status_line.refresh(current.name(), current.filetype());
+        let current = &self.documents[self.index];
+        self.status_line.refresh(current);
         self.status_line
             .render(ctx.clone(), buf, area.to_status_line());
         self.message_bar
@@ -42,21 +60,35 @@ impl Render for DocumentSwitcher {
     }
 }
 
-impl DocumentSwitcher {
-    pub fn default() -> Self {
+impl Default for DocumentSwitcher {
+    fn default() -> Self {
         Self {
             documents: Vec::new(),
             index: 0,
             empty: true,
             status_line: StatusLine::default(),
             message_bar: MessageBar::default(),
+            tab_width: DEFAULT_TAB_WIDTH,
+            swapped_at: Instant::now(),
         }
     }
+}
 
+impl DocumentSwitcher {
     pub fn message(&mut self, message: String) {
         self.message_bar = MessageBar::from(message);
     }
 
+    /// show a message until [`DocumentSwitcher::clear_message`] is called.
+    pub fn prompt(&mut self, message: String) {
+        self.message_bar = MessageBar::sticky(message);
+    }
+
+    /// hide the message bar.
+    pub fn clear_message(&mut self) {
+        self.message_bar = MessageBar::default();
+    }
+
     /// check
     pub fn is_empty(&self) -> bool {
         self.empty
@@ -92,33 +124,73 @@ impl DocumentSwitcher {
         }
     }
 
-    /// 获取指定行并转换为line
-    pub fn current_doc_row_to_line(&self, index: usize) -> Line {
+    /// 获取指定行并转换为line, 很长的行只转换从第`start`个字素簇开始的部分
+    pub fn current_doc_row_to_line(&self, index: usize, start: usize) -> Line {
         match self.current() {
             None => Line::default(),
-            Some(doc) => doc.line(index).to_line(),
+            Some(doc) => doc.row_window(index, start),
+        }
+    }
+
+    /// 光标所在的显示列, 从行首开始计算, 见[`Document::column`]
+    pub fn current_doc_column(&self, pos: Position) -> usize {
+        match self.current() {
+            None => 0,
+            Some(doc) => doc.column(pos),
+        }
+    }
+
+    /// 第`index`行显示列`column`所在的字素簇, 见[`Document::grapheme_at_column`]
+    pub fn current_doc_grapheme_at_column(&self, index: usize, column: usize) -> usize {
+        match self.current() {
+            None => 0,
+            Some(doc) => doc.grapheme_at_column(index, column),
         }
     }
 
     /// 获取指定行
-    pub fn current_doc_row(&self, index: usize) -> RopeSlice {
+    pub fn current_doc_row(&self, index: usize) -> RopeSlice<'_> {
         match self.current() {
             None => RopeSlice::from(""),
             Some(doc) => doc.line(index),
         }
     }
 
+    /// 获取指定行的字素簇数量,不包含换行符
+    pub fn current_doc_line_len(&self, index: usize) -> usize {
+        self.current_doc_line_len_at_most(index, usize::MAX)
+    }
+
+    /// 同[`DocumentSwitcher::current_doc_line_len`], 最多数到`max`
+    pub fn current_doc_line_len_at_most(&self, index: usize, max: usize) -> usize {
+        match self.current() {
+            None => 0,
+            Some(doc) => doc.line_len_at_most(index, max),
+        }
+    }
+
     /// 获取指定行的长度，根据字素簇边界分割
     pub fn get_row_width_split_by_word_boundary(&self, row: usize) -> usize {
         match self.current() {
-            None => (0),
-            Some(doc) => (doc.line_width(row)),
+            None => 0,
+            Some(doc) => doc.line_width(row),
+        }
+    }
+
+    /// set the tab width of the open documents and the ones added later.
+    pub fn set_tab_width(&mut self, tab_width: usize) {
+        self.tab_width = tab_width;
+        for doc in self.documents.iter_mut() {
+            doc.set_tab_width(tab_width);
         }
     }
 
     /// add text to last.
-    pub fn add(&mut self, doc: Document) {
-        let doc_empty = doc.is_empty();
+    ///
+    /// an unnamed empty document doesn't count, the banner stays until there is something to edit.
+    pub fn add(&mut self, mut doc: Document) {
+        doc.set_tab_width(self.tab_width);
+        let doc_empty = doc.is_empty() && doc.is_loaded() && doc.name() == DEFAULT_FILENAME;
         self.documents.push(doc);
         self.update_empty(doc_empty)
     }
@@ -134,6 +206,9 @@ impl DocumentSwitcher {
         if self.documents.is_empty() {
             self.empty = true;
         }
+        if self.index >= self.documents.len() {
+            self.index = self.documents.len().saturating_sub(1);
+        }
         result
     }
 
@@ -171,6 +246,23 @@ impl DocumentSwitcher {
         self.documents.get(index)
     }
 
+    /// get text by index to change it.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Document> {
+        self.documents.get_mut(index)
+    }
+
+    /// the index of the current text.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// move to the text at `index`.
+    pub fn select(&mut self, index: usize) {
+        if index < self.documents.len() {
+            self.index = index;
+        }
+    }
+
     /// get current text.
     pub fn current_mut(&mut self) -> Option<&mut Document> {
         self.documents.get_mut(self.index)
@@ -185,13 +277,148 @@ impl DocumentSwitcher {
         self.documents.len()
     }
 
-    /// load files
+    /// save current document, the result is shown in the message bar.
+    ///
+    /// returns whether the document was written.
+    pub fn save(&mut self) -> bool {
+        let (message, saved) = match self.current_mut() {
+            None => return false,
+            Some(doc) => match doc.save() {
+                Ok(_) => (format!("\"{}\" {}L written", doc.name(), doc.len()), true),
+                Err(err) => (format!("\"{}\" save failed: {}", doc.name(), err), false),
+            },
+        };
+
+        self.message(message);
+        saved
+    }
+
+    /// save every document with unsaved changes, the result is shown in the message bar.
+    ///
+    /// the others are left alone, so an unchanged file keeps its modification time and
+    /// a buffer without a file (e.g. stdin) or one still loading doesn't fail it.
+    /// returns whether all modified documents were written.
+    pub fn save_all(&mut self) -> bool {
+        let (mut written, mut failed) = (0, Vec::new());
+        for doc in self.documents.iter_mut() {
+            if !doc.is_modified() || !doc.is_loaded() {
+                continue;
+            }
+            match doc.save() {
+                Ok(_) => written += 1,
+                Err(err) => failed.push(format!("\"{}\": {}", doc.name(), err)),
+            }
+        }
+
+        let message = if failed.is_empty() {
+            format!("{} files written", written)
+        } else {
+            format!("save failed: {}", failed.join(", "))
+        };
+
+        self.message(message);
+        failed.is_empty()
+    }
+
+    /// how many documents have unsaved changes.
+    pub fn modified_count(&self) -> usize {
+        self.documents
+            .iter()
+            .filter(|doc| doc.is_modified())
+            .count()
+    }
+
+    /// load files, a path that doesn't exist yet gets an empty buffer that's created on the first save.
+    ///
+    /// `-` reads stdin when it's a pipe, see [`Document::stdin`].
+    /// the new files and the ones that can't be opened are shown in the message bar.
     pub fn load(&mut self, filenames: Vec<String>) {
+        // the keys are still read from the terminal: crossterm reads them from `/dev/tty`
+        // when stdin isn't one, `cmd | raw -` depends on it.
+        let stdin = (!io::stdin().is_terminal()).then(io::stdin);
+        self.load_with_stdin(filenames, stdin);
+    }
+
+    /// like [`DocumentSwitcher::load`], `-` reads `stdin`, `None` when it's a terminal.
+    pub fn load_with_stdin<R>(&mut self, filenames: Vec<String>, mut stdin: Option<R>)
+    where
+        R: io::Read + Send + 'static,
+    {
+        let mut messages = Vec::new();
+        let terminal = stdin.is_none();
         for filename in filenames {
-            if let Ok(doc) = Document::open(filename.as_str()) {
-                self.add(doc)
+            if filename == "-" {
+                match stdin.take() {
+                    Some(reader) => self.add(Document::from_reader(reader)),
+                    None if terminal => {
+                        messages.push("\"-\" stdin is a terminal, nothing to read".to_string())
+                    }
+                    None => messages.push("\"-\" stdin can only be read once".to_string()),
+                }
+                continue;
+            }
+
+            match Document::open(filename.as_str()) {
+                Ok(doc) => {
+                    if let Some(pid) = doc.editing_pid() {
+                        messages.push(format!(
+                            "\"{}\" is being edited by another raw (pid {})",
+                            filename, pid
+                        ));
+                    }
+                    if let Some(error) = doc.swap_error() {
+                        messages.push(format!("\"{}\" {}", filename, error));
+                    }
+                    self.add(doc)
+                }
+                Err(err) if is_not_found(&err) => {
+                    self.add(Document::new_file(&filename));
+                    messages.push(format!("\"{}\" [new file]", filename));
+                }
+                Err(err) => messages.push(format!("\"{}\" open failed: {}", filename, err)),
             }
         }
+
+        if !messages.is_empty() {
+            self.message(messages.join(", "));
+        }
+    }
+
+    /// write the unsaved changes of every document to its swap file, at most every [`SWAP_INTERVAL`].
+    pub fn write_swaps(&mut self) {
+        if self.swapped_at.elapsed() < SWAP_INTERVAL {
+            return;
+        }
+
+        self.swapped_at = Instant::now();
+        for doc in self.documents.iter_mut() {
+            doc.write_swap();
+        }
+    }
+
+    /// remove the swap files, the editor quits and the unsaved changes are discarded.
+    pub fn remove_swaps(&mut self) {
+        for doc in self.documents.iter_mut() {
+            doc.remove_swap();
+        }
+    }
+
+    /// append what the background loaders have read, the current document first.
+    pub fn poll_loaders(&mut self) {
+        let index = self.index;
+        let mut order: Vec<usize> = (0..self.documents.len()).collect();
+        order.sort_by_key(|i| *i != index);
+
+        for i in order {
+            if let Some(message) = self.documents[i].poll_loader(LOAD_BUDGET) {
+                self.message(message);
+            }
+        }
+    }
+
+    /// render only the message bar, at the bottom of `area`, e.g. over the banner.
+    pub fn render_message(&mut self, ctx: AppCtx, buf: &mut Buffer, area: Rect) {
+        self.message_bar.render(ctx, buf, area.to_status_line());
     }
 
     fn update_empty(&mut self, doc_empty: bool) {
@@ -205,4 +432,9 @@ impl DocumentSwitcher {
 
         self.empty = doc_empty;
     }
-}
\ No newline at end of file
+}
+
+fn is_not_found(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<io::Error>()
+        .is_some_and(|err| err.kind() == io::ErrorKind::NotFound)
+}
@@ -1,9 +1,13 @@
-use std::io::stdout;
+use std::cmp::Ordering;
+use std::io::{stdout, Write};
 
 use crossterm::terminal::Clear;
 use crossterm::terminal::ClearType::All;
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -11,6 +15,7 @@ use tui::backend::CrosstermBackend;
 use tui::buffer::Buffer;
 
 use crate::app::AppResult;
+use crate::clipboard;
 
 type Terminal = tui::terminal::Terminal<CrosstermBackend<std::io::Stdout>>;
 
@@ -21,12 +26,25 @@ pub struct Screen {
 impl Screen {}
 
 /// relative position of the current cursor
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+/// positions are ordered as they appear in the document, line first.
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Default for Screen {
     fn default() -> Self {
         Self {
@@ -50,6 +68,25 @@ impl Screen {
         Ok(())
     }
 
+    /// put `text` into the system clipboard with an OSC 52 sequence.
+    pub fn set_clipboard(&mut self, text: &str) -> AppResult<()> {
+        self.write(&clipboard::copy_sequence(text))
+    }
+
+    /// ask the terminal for the system clipboard, the answer arrives as key events,
+    /// see [`clipboard::Read`].
+    pub fn request_clipboard(&mut self) -> AppResult<()> {
+        self.write(clipboard::REQUEST)
+    }
+
+    fn write(&mut self, sequence: &str) -> AppResult<()> {
+        let backend = self.terminal.backend_mut();
+        backend.write_all(sequence.as_bytes())?;
+        backend.flush()?;
+
+        Ok(())
+    }
+
     /// get current buf
     pub fn get_buf(&mut self) -> &mut Buffer {
         self.terminal.current_buffer_mut()
@@ -86,6 +123,8 @@ pub fn init() -> AppResult<()> {
     execute!(
         stdout(),
         EnableMouseCapture,
+        EnableBracketedPaste,
+        EnableFocusChange,
         EnterAlternateScreen,
         crossterm::cursor::EnableBlinking,
         crossterm::cursor::SetCursorShape(crossterm::cursor::CursorShape::Block)
@@ -98,6 +137,8 @@ pub fn exit() -> AppResult<()> {
     execute!(
         stdout(),
         DisableMouseCapture,
+        DisableBracketedPaste,
+        DisableFocusChange,
         LeaveAlternateScreen,
         Clear(All),
         crossterm::cursor::SetCursorShape(crossterm::cursor::CursorShape::Block)
@@ -106,4 +147,4 @@ pub fn exit() -> AppResult<()> {
     disable_raw_mode()?;
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,323 @@
+use crate::extension::rope::Line;
+use crate::screen::Position;
+
+/// a selected range, `anchor` stays where the selection started and `head` follows the cursor.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Range {
+    pub anchor: Position,
+    pub head: Position,
+}
+
+impl Range {
+    pub fn new(anchor: Position, head: Position) -> Self {
+        Self { anchor, head }
+    }
+
+    /// an empty range at `pos`.
+    pub fn point(pos: Position) -> Self {
+        Self::new(pos, pos)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// the first position of the range.
+    pub fn start(&self) -> Position {
+        self.anchor.min(self.head)
+    }
+
+    /// the position after the last selected grapheme.
+    pub fn end(&self) -> Position {
+        self.anchor.max(self.head)
+    }
+
+    /// the selected graphemes `[from, to)` of line `y`, `to` is `None` when the selection
+    /// continues past the end of the line.
+    pub fn line_span(&self, y: usize) -> Option<(usize, Option<usize>)> {
+        let (start, end) = (self.start(), self.end());
+        if self.is_empty() || y < start.y || y > end.y {
+            return None;
+        }
+
+        let from = if y == start.y { start.x } else { 0 };
+        let to = if y == end.y { Some(end.x) } else { None };
+        Some((from, to))
+    }
+}
+
+/// the cursors of a document, every cursor is a range that may be empty.
+///
+/// the ranges are sorted and never overlap, the primary range is the one the screen follows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    ranges: Vec<Range>,
+    primary: usize,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self::point(Position::default())
+    }
+}
+
+impl Selection {
+    pub fn new(ranges: Vec<Range>, primary: usize) -> Self {
+        let mut selection = Self { ranges, primary };
+        if selection.ranges.is_empty() {
+            selection.ranges.push(Range::default());
+        }
+        selection.primary = selection.primary.min(selection.ranges.len() - 1);
+        selection.normalize();
+        selection
+    }
+
+    /// a single cursor at `pos`.
+    pub fn point(pos: Position) -> Self {
+        Self::single(Range::point(pos))
+    }
+
+    pub fn single(range: Range) -> Self {
+        Self {
+            ranges: vec![range],
+            primary: 0,
+        }
+    }
+
+    pub fn ranges(&self) -> &[Range] {
+        &self.ranges
+    }
+
+    pub fn primary(&self) -> Range {
+        self.ranges[self.primary]
+    }
+
+    pub fn primary_index(&self) -> usize {
+        self.primary
+    }
+
+    /// the cursor of the primary range.
+    pub fn cursor(&self) -> Position {
+        self.primary().head
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.iter().all(Range::is_empty)
+    }
+
+    /// add a range and make it the primary one.
+    pub fn push(&mut self, range: Range) {
+        self.ranges.push(range);
+        self.primary = self.ranges.len() - 1;
+        self.normalize();
+    }
+
+    /// replace the primary range.
+    pub fn set_primary(&mut self, range: Range) {
+        self.ranges[self.primary] = range;
+        self.normalize();
+    }
+
+    /// transform every range.
+    pub fn transform<F>(&mut self, f: F)
+    where
+        F: FnMut(&Range) -> Range,
+    {
+        self.ranges = self.ranges.iter().map(f).collect();
+        self.normalize();
+    }
+
+    /// drop every range but the primary one.
+    pub fn collapse(&mut self) {
+        *self = Self::single(self.primary());
+    }
+
+    /// sort the ranges and merge the ones that overlap, a merged range stays primary
+    /// if any of its parts was.
+    fn normalize(&mut self) {
+        let primary = self.ranges[self.primary];
+        let mut ranges = std::mem::take(&mut self.ranges);
+        ranges.sort_by_key(Range::start);
+
+        let mut merged: Vec<Range> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if overlaps(last, &range) => {
+                    let is_primary = range == primary || *last == primary;
+                    let (start, end) = (last.start(), last.end().max(range.end()));
+                    *last = if last.head < last.anchor {
+                        Range::new(end, start)
+                    } else {
+                        Range::new(start, end)
+                    };
+                    if is_primary {
+                        self.primary = merged.len() - 1;
+                    }
+                }
+                _ => {
+                    if range == primary {
+                        self.primary = merged.len();
+                    }
+                    merged.push(range);
+                }
+            }
+        }
+
+        self.ranges = merged;
+    }
+}
+
+/// a rectangular selection, `x` of its corners is a display column instead of a grapheme index.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub anchor: Position,
+    pub head: Position,
+}
+
+impl Block {
+    /// an empty block at display column `pos.x` of line `pos.y`.
+    pub fn point(pos: Position) -> Self {
+        Self {
+            anchor: pos,
+            head: pos,
+        }
+    }
+
+    /// the block as one range per line, `line` gives line `y` of the document.
+    ///
+    /// a double-width glyph partly inside of the block is selected whole, lines that end
+    /// before the block are skipped but the line of the head always gets a cursor.
+    pub fn selection<F>(&self, line: F) -> Selection
+    where
+        F: Fn(usize) -> Line,
+    {
+        let (left, right) = (
+            self.anchor.x.min(self.head.x),
+            self.anchor.x.max(self.head.x),
+        );
+        let (top, bottom) = (
+            self.anchor.y.min(self.head.y),
+            self.anchor.y.max(self.head.y),
+        );
+
+        let mut ranges = Vec::new();
+        let mut primary = 0;
+        for y in top..=bottom {
+            let line = line(y);
+            let len = line.len();
+            let start = match line.grapheme_at(left) {
+                Some(start) => start,
+                None if y == self.head.y => len,
+                None => continue,
+            };
+            let end = match left == right {
+                true => start,
+                false => (start..len)
+                    .find(|x| line.get_offset(*x) >= right)
+                    .unwrap_or(len),
+            };
+
+            let (start, end) = (Position { x: start, y }, Position { x: end, y });
+            if y == self.head.y {
+                primary = ranges.len();
+            }
+            ranges.push(match self.head.x < self.anchor.x {
+                true => Range::new(end, start),
+                false => Range::new(start, end),
+            });
+        }
+
+        Selection::new(ranges, primary)
+    }
+}
+
+/// `next` starts inside of `prev`, or both are cursors at the same position.
+fn overlaps(prev: &Range, next: &Range) -> bool {
+    next.start() < prev.end()
+        || next.start() == prev.start()
+        || (next.is_empty() && next.start() == prev.end())
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::RopeSlice;
+
+    use crate::extension::rope::RopeSliceEx;
+    use crate::screen::Position;
+    use crate::selection::{Block, Range, Selection};
+
+    /// the cursor position `x`, `y`.
+    fn pos(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    #[test]
+    fn test_line_span() {
+        let range = Range::new(Position { x: 3, y: 2 }, Position { x: 1, y: 0 });
+        assert_eq!(range.start(), Position { x: 1, y: 0 });
+        assert_eq!(range.line_span(0), Some((1, None)));
+        assert_eq!(range.line_span(1), Some((0, None)));
+        assert_eq!(range.line_span(2), Some((0, Some(3))));
+        assert_eq!(range.line_span(3), None);
+        assert_eq!(Range::point(Position { x: 1, y: 0 }).line_span(0), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut selection = Selection::point(pos(4, 0));
+        selection.push(Range::point(pos(0, 1)));
+        selection.push(Range::new(pos(1, 0), pos(5, 0)));
+        assert_eq!(selection.len(), 2);
+        assert_eq!(selection.primary(), Range::new(pos(1, 0), pos(5, 0)));
+        assert_eq!(selection.ranges()[1], Range::point(pos(0, 1)));
+
+        selection.transform(|_| Range::point(pos(0, 0)));
+        assert_eq!(selection, Selection::point(pos(0, 0)));
+    }
+
+    #[test]
+    fn test_block() {
+        let lines = ["a好b\n", "abcd\n", "a\n", "好好"];
+        let line = |y: usize| RopeSlice::from(lines[y]).to_line();
+
+        // column 2 is the right half of 好, the glyph is selected whole.
+        let block = Block {
+            anchor: pos(2, 0),
+            head: pos(3, 3),
+        };
+        let selection = block.selection(line);
+        assert_eq!(
+            selection.ranges(),
+            [
+                Range::new(pos(1, 0), pos(2, 0)),
+                Range::new(pos(2, 1), pos(3, 1)),
+                Range::new(pos(1, 3), pos(2, 3)),
+            ]
+        );
+        assert_eq!(selection.primary(), Range::new(pos(1, 3), pos(2, 3)));
+
+        // an empty block is a column of cursors, the short line only keeps the head.
+        let block = Block {
+            anchor: pos(1, 1),
+            head: pos(1, 2),
+        };
+        assert_eq!(
+            block.selection(line).ranges(),
+            [Range::point(pos(1, 1)), Range::point(pos(1, 2))]
+        );
+        let block = Block {
+            anchor: pos(3, 1),
+            head: pos(3, 0),
+        };
+        assert_eq!(block.selection(line).len(), 2);
+        let block = Block {
+            anchor: pos(3, 2),
+            head: pos(3, 1),
+        };
+        assert_eq!(block.selection(line).ranges(), [Range::point(pos(3, 1))]);
+    }
+}
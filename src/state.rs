@@ -0,0 +1,136 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{self, Path, PathBuf};
+use std::sync::RwLock;
+
+use ropey::Rope;
+
+/// the directory set by [`set_state_dir`], it replaces the per-user one.
+static STATE_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// keep the state in `dir` instead of the per-user directory, e.g. tests don't touch the real one.
+pub fn set_state_dir(dir: PathBuf) {
+    *STATE_DIR.write().unwrap() = Some(dir);
+}
+
+/// the per-user state directory: `$XDG_STATE_HOME/raw`, or `~/.local/state/raw`.
+pub fn state_dir() -> Option<PathBuf> {
+    if let Some(dir) = STATE_DIR.read().unwrap().clone() {
+        return Some(dir);
+    }
+    if let Some(dir) = env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("raw"));
+    }
+
+    env::var_os("HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(|home| PathBuf::from(home).join(".local/state/raw"))
+}
+
+/// the state file of `kind` (e.g. `undo`) that belongs to the document at `path`.
+///
+/// the file is named after the hash of the absolute path, it's canonical once the file exists.
+pub fn state_file(kind: &str, path: &Path) -> Option<PathBuf> {
+    let path = path.canonicalize().or_else(|_| path::absolute(path)).ok()?;
+    let dir = state_dir()?.join(kind);
+
+    Some(dir.join(format!(
+        "{:016x}",
+        hash(path.as_os_str().to_string_lossy().as_bytes())
+    )))
+}
+
+/// create (or truncate) a state file, it holds the text of documents that may be private:
+/// only the user can read it (0600) and the directories created for it (0700).
+pub fn create_file(file: &Path) -> io::Result<fs::File> {
+    if let Some(dir) = file.parent() {
+        let mut builder = fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(file)?;
+    // the mode only applies to a new file, an older one may still be readable by others.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    Ok(file)
+}
+
+/// 64-bit FNV-1a, unlike [`std::hash::DefaultHasher`] it's stable across builds,
+/// so it can be written to disk.
+#[derive(Copy, Clone, Debug)]
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// hash bytes with [`Fnv`].
+pub fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// hash the content of a rope with [`Fnv`], chunk by chunk.
+pub fn hash_rope(content: &Rope) -> u64 {
+    let mut hasher = Fnv::default();
+    for chunk in content.chunks() {
+        hasher.write(chunk.as_bytes());
+    }
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::state::{create_file, hash, hash_rope};
+
+    #[test]
+    fn test_hash() {
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+
+        let text = "raw ".repeat(10000);
+        assert_eq!(hash_rope(&Rope::from(text.as_str())), hash(text.as_bytes()));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_create_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("raw-state-{}", std::process::id()));
+        let file = dir.join("kind").join("file");
+        create_file(&file).unwrap();
+
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&file), 0o600);
+        assert_eq!(mode(file.parent().unwrap()), 0o700);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//! swap files: the unsaved changes of a document, written now and then so they survive a crash.
+
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use ropey::Rope;
+
+use crate::app::AppResult;
+use crate::state::{create_file, state_file};
+
+/// the directory in [`crate::state::state_dir`] that keeps the swap files.
+const SWAP_DIR: &str = "swap";
+
+/// the first line of a swap file, the version changes with the format.
+const SWAP_FILE_HEADER: &str = "raw swap 1";
+
+/// a swap file, written by the raw with `pid`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Swap {
+    pub pid: u32,
+    /// the whole content of the document
+    pub content: String,
+}
+
+/// the swap file of the document at `path`, it may not exist yet.
+pub fn swap_file(path: &Path) -> Option<PathBuf> {
+    state_file(SWAP_DIR, path)
+}
+
+/// write `content` to the swap file, through a temp file so a crash while writing keeps the last one.
+///
+/// it's only readable by the user, see [`create_file`].
+pub fn write(file: &Path, content: &Rope) -> AppResult<()> {
+    let tmp = file.with_extension("tmp");
+    let mut writer = BufWriter::new(create_file(&tmp)?);
+    writeln!(writer, "{}", SWAP_FILE_HEADER)?;
+    writeln!(writer, "{}", std::process::id())?;
+    for chunk in content.chunks() {
+        writer.write_all(chunk.as_bytes())?;
+    }
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(&tmp, file)?;
+    Ok(())
+}
+
+/// read the swap file written by [`write`], `None` when there is none.
+pub fn read(file: &Path) -> AppResult<Option<Swap>> {
+    let text = match fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut parts = text.splitn(3, '\n');
+    let (header, pid) = (parts.next(), parts.next().and_then(|pid| pid.parse().ok()));
+    match (header, pid, parts.next()) {
+        (Some(SWAP_FILE_HEADER), Some(pid), Some(content)) => Ok(Some(Swap {
+            pid,
+            content: content.to_string(),
+        })),
+        _ => bail!("not a swap file"),
+    }
+}
+
+/// is the process `pid` still running? it's asked with the signal 0, which is checked but never sent.
+///
+/// a process of another user can't be signaled but still runs.
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+    // SAFETY: the signal 0 doesn't touch the process, it only tells if it exists.
+    match unsafe { libc::kill(pid, 0) } {
+        0 => true,
+        _ => std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM),
+    }
+}
+
+/// without signals every process counts as gone, the swap file of a running raw is offered to recover.
+#[cfg(not(unix))]
+pub fn is_running(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use ropey::Rope;
+
+    use crate::swap::{is_running, read, write, Swap};
+
+    #[test]
+    fn test_write_and_read() {
+        let file = std::env::temp_dir()
+            .join(format!("raw-swap-{}", std::process::id()))
+            .join("swap");
+        assert_eq!(read(&file).unwrap(), None);
+
+        write(&file, &Rope::from("a\nb\n")).unwrap();
+        assert_eq!(
+            read(&file).unwrap(),
+            Some(Swap {
+                pid: std::process::id(),
+                content: "a\nb\n".to_string(),
+            })
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &std::path::Path| {
+                std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+            };
+            assert_eq!(mode(&file), 0o600);
+            assert_eq!(mode(file.parent().unwrap()), 0o700);
+        }
+
+        std::fs::write(&file, "something else").unwrap();
+        assert!(read(&file).is_err());
+        std::fs::remove_dir_all(file.parent().unwrap()).unwrap();
+
+        assert!(is_running(std::process::id()));
+        assert!(!is_running(u32::MAX));
+        assert!(!is_running(0));
+    }
+}
@@ -1,9 +1,30 @@
-use raw::render::document::{Document};
-use raw::render::Render;
+use raw::disk::DiskChange;
+use raw::extension::word::WordKind;
+use raw::history::ViewState;
+use raw::indent::Indent;
+use raw::line_ending::LineEnding;
+use raw::render::document::Document;
 use raw::render::switcher::DocumentSwitcher;
+use raw::render::Render;
+use raw::screen::Position;
+use raw::DEFAULT_FILENAME;
+use raw::{state, swap};
+use ropey::Rope;
+
+/// keep the undo history and swap files of the tests out of the real state directory.
+fn isolate_state() {
+    let dir = std::env::temp_dir().join(format!("raw-test-state-{}", std::process::id()));
+    state::set_state_dir(dir);
+}
+
+/// the cursor position `x`, `y`.
+fn pos(x: usize, y: usize) -> Position {
+    Position { x, y }
+}
 
 #[test]
 fn test_doc() {
+    isolate_state();
     let banner = Document::open("./src/banner").unwrap();
     println!("{:?}", banner.content.line(1));
 }
@@ -11,7 +32,7 @@ fn test_doc() {
 #[test]
 fn test_empty_default() {
     let container = DocumentSwitcher::default();
-    assert_eq!(container.is_empty(), true)
+    assert!(container.is_empty())
 }
 
 #[test]
@@ -20,20 +41,22 @@ fn test_empty_add() {
 
     container.add(Document::default());
 
-    assert_eq!(container.is_empty(), true)
+    assert!(container.is_empty())
 }
 
 #[test]
 fn test_empty_add_text() {
+    isolate_state();
     let mut container = DocumentSwitcher::default();
     let test_1 = Document::open("./src/banner");
     container.add(test_1.unwrap());
 
-    assert_eq!(container.is_empty(), false)
+    assert!(!container.is_empty())
 }
 
 #[test]
 fn test_remove_and_add_and_size() {
+    isolate_state();
     let mut container = DocumentSwitcher::default();
     container.add(Document::open("./src/banner").unwrap());
     container.add(Document::open("./src/screen.rs").unwrap());
@@ -46,12 +69,13 @@ fn test_remove_and_add_and_size() {
     assert_eq!(container.current().unwrap().name(), "./src/screen.rs");
     container.remove_current();
 
-    assert_eq!(container.is_empty(), true);
+    assert!(container.is_empty());
     assert_eq!(container.size(), 0);
 }
 
 #[test]
 fn test_move() {
+    isolate_state();
     let mut container = DocumentSwitcher::default();
     container.add(Document::open("./src/banner").unwrap());
     container.add(Document::open("./src/screen.rs").unwrap());
@@ -74,4 +98,441 @@ fn test_move() {
     assert_eq!(container.current().unwrap().name(), "./src/screen.rs");
     container.prev();
     assert_eq!(container.current().unwrap().name(), "./src/banner");
-}
\ No newline at end of file
+}
+#[test]
+fn test_save_keeps_permissions() {
+    isolate_state();
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join(format!("raw-save-{}", std::process::id()));
+    std::fs::write(&path, "old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+
+    let mut doc = Document::open(path.to_str().unwrap()).unwrap();
+    doc.insert(Position { x: 0, y: 0 }, "new ");
+    doc.save().unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new old");
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_save_unnamed() {
+    isolate_state();
+    assert!(Document::default().save().is_err());
+
+    // a diff is shown under a name that isn't a file.
+    let name = format!("raw-scratch-{}.diff", std::process::id());
+    let mut doc = Document::scratch(Rope::from("+a\n"), &name);
+    assert!(doc.is_read_only());
+    assert!(doc.save().is_err());
+    assert!(doc.reload(ViewState::default()).is_err());
+    assert!(!std::path::Path::new(&name).exists());
+}
+
+#[test]
+fn test_modified() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-modified-{}", std::process::id()));
+    std::fs::write(&path, "abc").unwrap();
+
+    let mut container = DocumentSwitcher::default();
+    container.add(Document::open(path.to_str().unwrap()).unwrap());
+    container.add(Document::open("./src/banner").unwrap());
+    assert_eq!(container.modified_count(), 0);
+
+    let doc = container.current_mut().unwrap();
+    doc.insert(Position { x: 3, y: 0 }, "d");
+    assert!(doc.is_modified());
+    assert_eq!(container.modified_count(), 1);
+
+    assert!(container.save());
+    assert!(!container.current().unwrap().is_modified());
+    assert_eq!(container.modified_count(), 0);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_save_all() {
+    isolate_state();
+    let dir = std::env::temp_dir().join(format!("raw-save-all-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (changed, unchanged) = (dir.join("changed"), dir.join("unchanged"));
+    std::fs::write(&changed, "a").unwrap();
+    std::fs::write(&unchanged, "b").unwrap();
+    let modified = std::fs::metadata(&unchanged).unwrap().modified().unwrap();
+
+    let mut container = DocumentSwitcher::default();
+    container.add(Document::open(changed.to_str().unwrap()).unwrap());
+    container.add(Document::open(unchanged.to_str().unwrap()).unwrap());
+    // a buffer without a file, like `raw -`.
+    container.add(Document::from(Rope::from("c"), DEFAULT_FILENAME));
+    container
+        .current_mut()
+        .unwrap()
+        .insert(Position { x: 1, y: 0 }, "!");
+
+    assert!(container.save_all());
+    assert_eq!(std::fs::read_to_string(&changed).unwrap(), "a!");
+    let after = std::fs::metadata(&unchanged).unwrap().modified().unwrap();
+    assert_eq!(after, modified);
+    assert_eq!(container.modified_count(), 0);
+
+    // a modified buffer without a file can't be saved.
+    container.next();
+    container.next();
+    container
+        .current_mut()
+        .unwrap()
+        .insert(Position { x: 1, y: 0 }, "!");
+    assert!(!container.save_all());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_remove_last() {
+    isolate_state();
+    let mut container = DocumentSwitcher::default();
+    container.add(Document::open("./src/banner").unwrap());
+    container.add(Document::open("./src/screen.rs").unwrap());
+
+    container.prev();
+    container.remove_current();
+    assert_eq!(container.current().unwrap().name(), "./src/banner");
+}
+
+#[test]
+fn test_save_keeps_encoding() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-encoding-{}", std::process::id()));
+    // "你好" in GBK.
+    std::fs::write(&path, b"\xc4\xe3\xba\xc3\n").unwrap();
+
+    let mut doc = Document::open(path.to_str().unwrap()).unwrap();
+    assert_eq!(doc.encoding_name(), "GBK");
+    assert_eq!(doc.content.to_string(), "你好\n");
+    doc.insert(Position { x: 2, y: 0 }, "!");
+    doc.save().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"\xc4\xe3\xba\xc3!\n");
+
+    doc.reopen(encoding_rs::WINDOWS_1252).unwrap();
+    assert_eq!(doc.content.to_string(), "ÄãºÃ!\n");
+    doc.save().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"\xc4\xe3\xba\xc3!\n");
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_load_new_file() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-new-{}.txt", std::process::id()));
+    let name = path.to_str().unwrap().to_string();
+
+    let mut container = DocumentSwitcher::default();
+    container.load(vec![
+        name.clone(),
+        std::env::temp_dir().to_str().unwrap().to_string(),
+    ]);
+    assert_eq!(container.size(), 1);
+    assert!(!container.is_empty());
+    assert!(!path.exists());
+
+    let doc = container.current_mut().unwrap();
+    assert_eq!(doc.name(), name);
+    doc.insert(Position { x: 0, y: 0 }, "new");
+
+    // the unsaved text is in the swap file before the first save.
+    let file = swap::swap_file(&path).unwrap();
+    doc.write_swap();
+    assert_eq!(swap::read(&file).unwrap().unwrap().content, "new");
+    std::fs::write(&file, "raw swap 1\n4294967295\nnew\n").unwrap();
+    assert_eq!(Document::new_file(&name).recovery(), Some("new\n"));
+
+    assert!(container.save());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+    assert!(!file.exists());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_load_binary() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-binary-{}.bin", std::process::id()));
+    let name = path.to_str().unwrap().to_string();
+    let bytes = [0x7f, b'E', b'L', b'F', 0, 0xff, b'\n'];
+    std::fs::write(&path, bytes).unwrap();
+
+    let mut container = DocumentSwitcher::default();
+    container.load(vec![name]);
+    let doc = container.current_mut().unwrap();
+    assert!(doc.is_binary());
+    assert_eq!(doc.row_bytes(0), bytes);
+    assert!(container.save());
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_load_large() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-large-{}.txt", std::process::id()));
+    let name = path.to_str().unwrap().to_string();
+    let text = "\tline\r\n".repeat(1000);
+    std::fs::write(&path, &text).unwrap();
+
+    let mut doc = Document::open_large(&name, None).unwrap();
+    assert!(!doc.is_loaded());
+    assert!(doc.save().is_err());
+
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !doc.is_loaded() && std::time::Instant::now() < deadline {
+        doc.poll_loader(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(doc.load_progress(), None);
+    assert_eq!(doc.content.to_string(), text);
+    assert_eq!(doc.len(), 1001);
+    assert_eq!(doc.line_ending(), LineEnding::Crlf);
+    assert_eq!(doc.indent(), Indent::Tabs);
+    assert!(doc.save().is_ok());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_load_stdin() {
+    let mut container = DocumentSwitcher::default();
+    container.load_with_stdin(vec!["-".to_string()], None::<std::io::Empty>);
+    assert!(container.is_empty());
+
+    let piped = std::io::Cursor::new("piped\ntext\n".repeat(1000).into_bytes());
+    // only the first `-` reads it, it can't be read twice.
+    container.load_with_stdin(vec!["-".to_string(), "-".to_string()], Some(piped));
+    assert_eq!(container.size(), 1);
+
+    let doc = container.current_mut().unwrap();
+    assert_eq!(doc.name(), DEFAULT_FILENAME);
+    assert_eq!(doc.load_progress(), None);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+    while !doc.is_loaded() && std::time::Instant::now() < deadline {
+        doc.poll_loader(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(doc.content.to_string(), "piped\ntext\n".repeat(1000));
+    assert!(!doc.is_modified());
+}
+
+#[test]
+fn test_reload() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-reload-{}.txt", std::process::id()));
+    let name = path.to_str().unwrap().to_string();
+    std::fs::write(&path, "abc\n").unwrap();
+
+    let mut doc = Document::open(&name).unwrap();
+    assert_eq!(doc.check_disk(), DiskChange::Unchanged);
+    doc.insert(Position { x: 3, y: 0 }, "d");
+    doc.save().unwrap();
+    // its own save isn't a change.
+    assert_eq!(doc.check_disk(), DiskChange::Unchanged);
+
+    std::fs::write(&path, "theirs\r\n").unwrap();
+    assert!(matches!(doc.check_disk(), DiskChange::Changed(_)));
+    // it's reported once.
+    assert_eq!(doc.check_disk(), DiskChange::Unchanged);
+
+    doc.reload(ViewState::default()).unwrap();
+    assert_eq!(doc.content.to_string(), "theirs\r\n");
+    assert_eq!(doc.line_ending(), LineEnding::Crlf);
+    assert!(!doc.is_modified());
+
+    // the reload can be undone.
+    assert!(doc.undo().is_some());
+    assert_eq!(doc.content.to_string(), "abcd\n");
+    assert!(doc.is_modified());
+
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(doc.check_disk(), DiskChange::Deleted);
+    assert_eq!(doc.check_disk(), DiskChange::Unchanged);
+}
+
+#[test]
+fn test_swap() {
+    isolate_state();
+    let path = std::env::temp_dir().join(format!("raw-swap-{}.txt", std::process::id()));
+    let name = path.to_str().unwrap().to_string();
+    std::fs::write(&path, "abc\n").unwrap();
+    let file = swap::swap_file(&path).unwrap();
+    let crashed = |pid: u32| std::fs::write(&file, format!("raw swap 1\n{}\nabcd\n", pid)).unwrap();
+
+    let mut doc = Document::open(&name).unwrap();
+    doc.insert(Position { x: 3, y: 0 }, "d");
+    doc.write_swap();
+    assert_eq!(swap::read(&file).unwrap().unwrap().content, "abcd\n");
+    doc.undo();
+    doc.write_swap();
+    assert!(!file.exists());
+
+    // the raw that wrote it is gone.
+    crashed(u32::MAX);
+    let mut doc = Document::open(&name).unwrap();
+    assert_eq!(doc.recovery(), Some("abcd\n"));
+    doc.discard_recovery();
+    assert!(!file.exists());
+
+    crashed(u32::MAX);
+    let mut doc = Document::open(&name).unwrap();
+    assert!(doc.recover(ViewState::default()));
+    assert_eq!(doc.content.to_string(), "abcd\n");
+    assert!(doc.is_modified());
+    doc.write_swap();
+    assert_eq!(swap::read(&file).unwrap().unwrap().pid, std::process::id());
+    doc.save().unwrap();
+    assert!(!file.exists());
+
+    // pid 1 is always running.
+    crashed(1);
+    let doc = Document::open(&name).unwrap();
+    assert_eq!(doc.editing_pid(), Some(1));
+    assert_eq!(doc.recovery(), None);
+    std::fs::remove_file(&file).unwrap();
+
+    // the same file opened twice keeps the swap file of the first document.
+    let mut doc = Document::open(&name).unwrap();
+    doc.insert(Position { x: 3, y: 0 }, "d");
+    doc.write_swap();
+    let other = Document::open(&name).unwrap();
+    assert_eq!((other.editing_pid(), other.recovery()), (None, None));
+    assert_eq!(swap::read(&file).unwrap().unwrap().content, "abcdd\n");
+
+    // a reload drops the changes, the swap file is still the document's until it's written.
+    doc.reload(ViewState::default()).unwrap();
+    assert!(file.exists());
+    doc.write_swap();
+    assert!(!file.exists());
+
+    // a swap file that can't be read is reported and kept.
+    let newer = "raw swap 2\nsomething else\n";
+    std::fs::write(&file, newer).unwrap();
+    let mut doc = Document::open(&name).unwrap();
+    assert!(doc.swap_error().is_some());
+    doc.insert(Position { x: 3, y: 0 }, "d");
+    doc.write_swap();
+    doc.reopen(encoding_rs::UTF_8).unwrap();
+    assert!(doc.swap_error().is_some());
+    assert_eq!(std::fs::read_to_string(&file).unwrap(), newer);
+
+    std::fs::remove_file(&file).unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// every stop of the word motion from `from` on, until it doesn't move anymore.
+fn word_stops(doc: &Document, from: Position, kind: WordKind, forward: bool) -> Vec<Position> {
+    let mut stops = Vec::new();
+    let mut pos = from;
+    loop {
+        let next = doc.word_motion(pos, kind, forward);
+        if next == pos {
+            return stops;
+        }
+        stops.push(next);
+        pos = next;
+    }
+}
+
+#[test]
+fn test_word_motion() {
+    let row = |y, xs: &[usize]| xs.iter().map(|x| pos(*x, y)).collect::<Vec<_>>();
+    let doc = Document::from(
+        Rope::from("let x = a.b(c, d);\n\n  你好, 世界。test中文 👍🏽👍"),
+        "test",
+    );
+
+    // punctuation runs are one word, CJK runs too, an emoji with its modifier is one.
+    let mut expected = row(0, &[3, 5, 7, 11, 12, 13, 14, 16, 18]);
+    // the line breaks are stops of their own, also of an empty line.
+    expected.extend([pos(0, 1), pos(0, 2)]);
+    expected.extend(row(2, &[4, 5, 8, 9, 13, 15, 17, 18]));
+    assert_eq!(word_stops(&doc, pos(0, 0), WordKind::Word, true), expected);
+
+    let mut expected = row(2, &[17, 16, 13, 9, 8, 6, 4, 2, 0]);
+    expected.extend([pos(0, 1), pos(18, 0)]);
+    expected.extend(row(0, &[16, 15, 13, 12, 11, 8, 6, 4, 0]));
+    assert_eq!(
+        word_stops(&doc, pos(18, 2), WordKind::Word, false),
+        expected
+    );
+
+    // WORDs only end at whitespace.
+    let mut expected = row(0, &[3, 5, 7, 14, 18]);
+    expected.extend([pos(0, 1), pos(0, 2)]);
+    expected.extend(row(2, &[5, 15, 18]));
+    assert_eq!(
+        word_stops(&doc, pos(0, 0), WordKind::BigWord, true),
+        expected
+    );
+
+    let doc = Document::from(Rope::from("parseHTTPResponse_v2 snake_case"), "test");
+    assert_eq!(
+        word_stops(&doc, pos(0, 0), WordKind::Subword, true),
+        row(0, &[5, 9, 17, 20, 26, 31])
+    );
+    assert_eq!(
+        word_stops(&doc, pos(31, 0), WordKind::Subword, false),
+        row(0, &[27, 21, 18, 9, 5, 0])
+    );
+    assert_eq!(
+        word_stops(&doc, pos(0, 0), WordKind::Word, true),
+        row(0, &[20, 31])
+    );
+
+    // a binary document has no words.
+    let doc = Document::binary(Rope::from("ab cd"), "test");
+    assert_eq!(word_stops(&doc, pos(0, 0), WordKind::Word, true), []);
+}
+
+#[test]
+fn test_delete_word() {
+    let deleted = |text: &str, at: Position, forward: bool| {
+        let mut doc = Document::from(Rope::from(text), "test");
+        let cursor = doc.delete_word(at, WordKind::Word, forward);
+        (doc.content.to_string(), cursor)
+    };
+
+    // the line break goes alone.
+    assert_eq!(
+        deleted("foo bar\nbaz", pos(0, 1), false),
+        ("foo barbaz".to_string(), pos(7, 0))
+    );
+    assert_eq!(
+        deleted("foo bar\nbaz", pos(7, 0), true),
+        ("foo barbaz".to_string(), pos(7, 0))
+    );
+    // the whitespace before the word goes with it.
+    assert_eq!(
+        deleted("foo bar\nbaz", pos(3, 0), true),
+        ("foo\nbaz".to_string(), pos(3, 0))
+    );
+    assert_eq!(
+        deleted("foo bar\nbaz", pos(5, 0), false),
+        ("foo ar\nbaz".to_string(), pos(4, 0))
+    );
+    assert_eq!(
+        deleted("a.b(c);", pos(7, 0), false),
+        ("a.b(c".to_string(), pos(5, 0))
+    );
+    assert_eq!(
+        deleted("你好, 世界", pos(6, 0), false),
+        ("你好, ".to_string(), pos(4, 0))
+    );
+    assert_eq!(
+        deleted("  你好", pos(0, 0), true),
+        ("".to_string(), pos(0, 0))
+    );
+}